@@ -0,0 +1,47 @@
+//! Compares the owned ([`mmi_parser::parse_record`]) and borrowed
+//! ([`mmi_parser::parse_record_ref`]) parsing paths over the sample data.
+//!
+//! Run with `cargo bench`.
+
+use std::fs;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+fn load_lines() -> Vec<String> {
+    ["data/MMI_sample.txt", "data/AA_sample.txt"]
+        .iter()
+        .flat_map(|path| {
+            fs::read_to_string(path)
+                .expect("could not read sample data")
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let lines = load_lines();
+    let bytes = lines.iter().map(|line| line.len() as u64).sum();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(bytes));
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            for line in &lines {
+                black_box(mmi_parser::parse_record(black_box(line)).unwrap());
+            }
+        })
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            for line in &lines {
+                black_box(mmi_parser::parse_record_ref(black_box(line)).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -33,7 +33,7 @@ fn main() {
                     // utilize read lines buffer
                     let file = File::open(&path).expect("could not open file");
                     let reader = BufReader::new(file);
-                    for line in reader.lines().flatten() {
+                    for line in reader.lines().map_while(Result::ok) {
                         let result = mmi_parser::parse_record(&line);
                         if result.is_err() {
                             panic!("Example failed!")
@@ -0,0 +1,186 @@
+//! Builds MMI-style concept records out of the individual concept matches
+//! reported by MetaMap's richer output formats.
+//!
+//! Fielded MMI lists every concept once per document, with all of the triggers,
+//! sections and positions that produced it. The other formats report each match
+//! on its own, so the matches of a document are merged here by CUI. Matches
+//! reported without a CUI (such as those of human-readable output printed
+//! without `-I`) are merged by preferred name instead and get the
+//! [`Cui::missing`] placeholder, while matches with a malformed CUI are skipped
+//! with a warning.
+
+use crate::{
+    Cui, DocumentId, Location, MmiOutput, Occurrence, PositionalInfo, Section, Span, Trigger,
+};
+
+/// A single match of a concept in the text, as found in a mapping.
+#[derive(Debug)]
+pub(crate) struct ConceptMatch {
+    /// `None` when MetaMap did not report a CUI
+    pub(crate) cui: Option<String>,
+    pub(crate) preferred_name: String,
+    /// the concept string that matched
+    pub(crate) matched: String,
+    pub(crate) semantic_types: Vec<String>,
+    /// 0-1000, 1000 being a perfect match
+    pub(crate) score: f64,
+    pub(crate) section: Section,
+    /// number of the utterance within its section (starting with 1)
+    pub(crate) utterance_number: i32,
+    /// the text that matched
+    pub(crate) text: String,
+    pub(crate) part_of_speech: String,
+    pub(crate) negated: bool,
+    pub(crate) spans: Vec<Span>,
+}
+
+/// Spans of one occurrence along with the index of the trigger that matched them.
+type TriggerSpans = (usize, Vec<Span>);
+
+/// Merges the matches of one document into one record per concept, in order
+/// of first appearance.
+///
+/// The score of a record is the best score of its matches, since the MMI
+/// ranking itself cannot be reproduced outside of MetaMap. Tree codes are not
+/// part of these formats and are left empty.
+pub(crate) fn aggregate(id: &str, matches: Vec<ConceptMatch>) -> Vec<MmiOutput> {
+    // each record with the sections and occurrences collected for it so far
+    let mut records: Vec<(MmiOutput, Vec<Section>, Vec<TriggerSpans>)> = Vec::new();
+    let mut skipped = 0;
+    for m in matches {
+        let cui = match m.cui.as_deref().map(str::parse::<Cui>) {
+            Some(Ok(cui)) => cui,
+            Some(Err(_)) => {
+                skipped += 1;
+                continue;
+            }
+            None => Cui::missing(),
+        };
+        let same_concept =
+            |r: &MmiOutput| r.cui == cui && (!cui.is_missing() || r.name == m.preferred_name);
+        let index = match records.iter().position(|(r, ..)| same_concept(r)) {
+            Some(index) => index,
+            None => {
+                let record = MmiOutput {
+                    id: DocumentId::new_unchecked(id.to_string()),
+                    mmi: "MMI".to_string(),
+                    score: m.score,
+                    name: m.preferred_name.clone(),
+                    cui,
+                    semantic_types: m.semantic_types.clone(),
+                    triggers: Vec::new(),
+                    location: Location::new([]),
+                    positional_info: PositionalInfo::from_occurrences(Vec::new()),
+                    tree_codes: None,
+                };
+                records.push((record, Vec::new(), Vec::new()));
+                records.len() - 1
+            }
+        };
+        let (record, sections, occurrences) = &mut records[index];
+        record.score = record.score.max(m.score);
+        let trigger = Trigger {
+            name: m.matched,
+            loc: Location::from(m.section.clone()),
+            loc_position: m.utterance_number,
+            text: m.text,
+            part_of_speech: m.part_of_speech,
+            negation: m.negated,
+        };
+        let trigger_index = match record.triggers.iter().position(|t| *t == trigger) {
+            Some(index) => index,
+            None => {
+                record.triggers.push(trigger);
+                record.triggers.len() - 1
+            }
+        };
+        if !sections.contains(&m.section) {
+            sections.push(m.section);
+        }
+        // one positional group per trigger, as MetaMap writes them
+        let occurrence = (trigger_index, m.spans);
+        if !occurrence.1.is_empty() && !occurrences.contains(&occurrence) {
+            occurrences.push(occurrence);
+        }
+    }
+    if skipped > 0 {
+        log::warn!(
+            "Skipped {} concept match(es) with a malformed CUI in document {}",
+            skipped,
+            id
+        );
+    }
+    records
+        .into_iter()
+        .map(|(mut record, sections, occurrences)| {
+            let occurrences = occurrences
+                .into_iter()
+                .map(|(group, spans)| Occurrence::new(group, spans))
+                .collect();
+            record.location = Location::new(sections);
+            record.positional_info = PositionalInfo::from_occurrences(occurrences);
+            record
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PositionalInfoType;
+
+    fn concept(cui: &str, section: Section, score: f64, spans: Vec<Span>) -> ConceptMatch {
+        ConceptMatch {
+            cui: Some(cui.to_string()),
+            preferred_name: "Name".to_string(),
+            matched: "name".to_string(),
+            semantic_types: vec!["qlco".to_string()],
+            score,
+            section,
+            utterance_number: 1,
+            text: "name".to_string(),
+            part_of_speech: "noun".to_string(),
+            negated: false,
+            spans,
+        }
+    }
+
+    #[test]
+    fn test_aggregate_merges_by_cui() {
+        let records = aggregate(
+            "1",
+            vec![
+                concept("C0000001", Section::AB, 800.0, vec![Span::new(40, 4)]),
+                concept("C0000002", Section::TI, 1000.0, vec![Span::new(0, 4)]),
+                concept("C0000001", Section::TI, 900.0, vec![Span::new(5, 4)]),
+                concept("C0000001", Section::AB, 800.0, vec![Span::new(40, 4)]),
+            ],
+        );
+        assert_eq!(records.len(), 2);
+        let first = &records[0];
+        assert_eq!(first.cui.as_str(), "C0000001");
+        assert_eq!(first.score, 900.0);
+        assert_eq!(first.triggers.len(), 2);
+        assert_eq!(first.location.to_string(), "AB;TI");
+        assert_eq!(first.positional_info.case, PositionalInfoType::A);
+        assert_eq!(first.positional_info.to_string(), "40/4;5/4");
+        let locations = first.occurrence_locations();
+        assert_eq!(locations[0].unwrap().to_string(), "AB");
+        assert_eq!(locations[1].unwrap().to_string(), "TI");
+        assert_eq!(records[1].id.as_str(), "1");
+    }
+
+    #[test]
+    fn test_aggregate_skips_invalid_cuis() {
+        let records = aggregate(
+            "1",
+            vec![
+                concept("", Section::TI, 1000.0, Vec::new()),
+                concept("C0000001", Section::TI, 900.0, Vec::new()),
+                concept("C01", Section::TI, 900.0, Vec::new()),
+            ],
+        );
+        assert_eq!(records.len(), 1);
+        assert!(records[0].cui.is_valid());
+    }
+}
@@ -0,0 +1,405 @@
+//! Zero-copy, borrowed versions of the record types.
+//!
+//! [`parse_record_ref`] slices directly into the input line instead of allocating
+//! a `String` for every field, which matters when parsing hundreds of millions of lines.
+//! Trigger values are the only text that may need copying, when MetaMap's quotes
+//! appear inside of them, hence the [`Cow`]s in [`TriggerRef`].
+//!
+//! Every borrowed type has an `into_owned` method converting it into its
+//! owned counterpart, which is exactly what [`parse_record`](crate::parse_record) does.
+
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::{
+    check_parts, lite, offset_in, parse_positional_info, parse_semantic_types, parse_tree_codes,
+    parse_triggers, split_text, AaOutput, AaPosInfo, AbbreviationType, Cui, Dialect, DocumentId,
+    Location, MmiOutput, Output, ParseError, PositionalInfo, Result, Strictness, TreeCode, Trigger,
+    Warnings, AA_FIELDS, MMI_FIELDS,
+};
+
+/// Borrowed version of [`Trigger`].
+#[derive(PartialEq, Eq, Debug, Serialize)]
+pub struct TriggerRef<'a> {
+    /// UMLS concept name
+    pub name: Cow<'a, str>,
+    /// location of text
+    pub loc: Location,
+    /// number of the utterance within the location (starting with 1)
+    pub loc_position: i32,
+    /// the actual text
+    pub text: Cow<'a, str>,
+    /// determined by MedPost Tagger or Lexical Lookup
+    pub part_of_speech: Cow<'a, str>,
+    /// True if text is considered negated by MetaMap
+    pub negation: bool,
+}
+
+impl TriggerRef<'_> {
+    /// Converts into an owned [`Trigger`].
+    pub fn into_owned(self) -> Trigger {
+        Trigger {
+            name: self.name.into_owned(),
+            loc: self.loc,
+            loc_position: self.loc_position,
+            text: self.text.into_owned(),
+            part_of_speech: self.part_of_speech.into_owned(),
+            negation: self.negation,
+        }
+    }
+}
+
+/// Borrowed version of [`MmiOutput`].
+#[derive(Debug, PartialEq, Serialize)]
+pub struct MmiOutputRef<'a> {
+    /// unique identifier
+    pub id: &'a str,
+    /// always MMI
+    pub mmi: &'a str,
+    /// score of concept relevance, 0-1000, 1000 being perfect
+    pub score: f64,
+    /// name of the concept matched
+    pub name: &'a str,
+    /// CUI for identified UMLS concept
+    pub cui: &'a str,
+    /// Semantic Type abbreviations
+    pub semantic_types: Vec<&'a str>,
+    /// Triggers for MMI to flag this concept
+    pub triggers: Vec<TriggerRef<'a>>,
+    /// Location of concept
+    pub location: Location,
+    /// Positional information of concept
+    pub positional_info: PositionalInfo,
+    /// Optional MeSH [tree code(s)](https://www.nlm.nih.gov/mesh/meshhome.html)
+    pub tree_codes: Option<Vec<&'a str>>,
+}
+
+/// Validates the record id, which is always the first field.
+fn check_id(id: &str, warnings: &mut Warnings) -> Result<()> {
+    warnings.check(DocumentId::is_valid_str(id), || ParseError::InvalidValue {
+        field: "id",
+        value: id.to_string(),
+        column: 0,
+    })
+}
+
+/// Byte column at which each field starts when the fields are joined with pipes.
+fn field_columns<const N: usize>(fields: &[&str; N]) -> [usize; N] {
+    let mut columns = [0; N];
+    for i in 1..N {
+        columns[i] = columns[i - 1] + fields[i - 1].len() + 1;
+    }
+    columns
+}
+
+impl<'a> MmiOutputRef<'a> {
+    /// Assembles a borrowed MMI record from its ten fields, in line order.
+    pub(crate) fn from_fields(
+        fields: [&'a str; 10],
+        dialect: Dialect,
+        warnings: &mut Warnings,
+    ) -> Result<Self> {
+        let columns = field_columns(&fields);
+        check_id(fields[0], warnings)?;
+        warnings.check(Cui::is_valid_str(fields[4]), || ParseError::InvalidValue {
+            field: "cui",
+            value: fields[4].to_string(),
+            column: columns[4],
+        })?;
+        let score = fields[2]
+            .parse::<f64>()
+            .map_err(|_| ParseError::InvalidFloat {
+                field: "score",
+                value: fields[2].to_string(),
+                column: columns[2],
+            })?;
+        let triggers = match dialect {
+            Dialect::MetaMap => parse_triggers(fields[6], columns[6])?,
+            Dialect::Lite => lite::parse_triggers(fields[6], columns[6])?,
+        };
+        let location = if dialect == Dialect::Lite && fields[7].is_empty() {
+            lite::trigger_location(&triggers, columns[7])?
+        } else {
+            Location::from_str(fields[7]).map_err(|_| ParseError::UnknownLocation {
+                field: "location",
+                value: fields[7].to_string(),
+                column: columns[7],
+            })?
+        };
+        let positional_info = match dialect {
+            Dialect::MetaMap => parse_positional_info(fields[8], columns[8])?,
+            // same length, so the columns of the original line still apply
+            Dialect::Lite => parse_positional_info(&fields[8].replace(':', "/"), columns[8])?,
+        };
+        let tree_codes = parse_tree_codes(fields[9]);
+        for code in tree_codes.iter().flatten() {
+            warnings.check(TreeCode::is_valid_str(code), || ParseError::InvalidValue {
+                field: "tree_codes",
+                value: code.to_string(),
+                column: columns[9] + offset_in(fields[9], code),
+            })?;
+        }
+        Ok(MmiOutputRef {
+            id: fields[0],
+            mmi: fields[1],
+            score,
+            name: fields[3],
+            cui: fields[4],
+            semantic_types: parse_semantic_types(fields[5]),
+            triggers,
+            location,
+            positional_info,
+            tree_codes,
+        })
+    }
+
+    /// Converts into an owned [`MmiOutput`].
+    pub fn into_owned(self) -> MmiOutput {
+        MmiOutput {
+            id: DocumentId::new_unchecked(self.id.to_string()),
+            mmi: self.mmi.to_string(),
+            score: self.score,
+            name: self.name.to_string(),
+            cui: Cui::new_unchecked(self.cui.to_string()),
+            semantic_types: self
+                .semantic_types
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            triggers: self
+                .triggers
+                .into_iter()
+                .map(TriggerRef::into_owned)
+                .collect(),
+            location: self.location,
+            positional_info: self.positional_info,
+            tree_codes: self.tree_codes.map(|codes| {
+                codes
+                    .into_iter()
+                    .map(|code| TreeCode::new_unchecked(code.to_string()))
+                    .collect()
+            }),
+        }
+    }
+}
+
+/// Borrowed version of [`AaOutput`].
+#[derive(PartialEq, Eq, Debug, Serialize)]
+pub struct AaOutputRef<'a> {
+    /// Unique identifier
+    pub id: &'a str,
+    /// Abbreviation type: either MetaMap defined or User-defined
+    pub abbreviation_type: AbbreviationType,
+    /// Short form of the acronym/abbreviation
+    pub short_form: &'a str,
+    /// Long form or expansion
+    pub long_form: &'a str,
+    /// number of tokens (including whitespace) in short form
+    pub short_token_count: i32,
+    /// number of characters in short form
+    pub short_character_count: i32,
+    /// number of tokens (including whitespace) in long form
+    pub long_token_count: i32,
+    /// number of characters in long form
+    pub long_character_count: i32,
+    /// starting position of short form followed by ":" followed by character length of short form
+    pub positional_info: AaPosInfo,
+}
+
+impl<'a> AaOutputRef<'a> {
+    /// Assembles a borrowed AA record from its nine fields, in line order.
+    pub(crate) fn from_fields(fields: [&'a str; 9], warnings: &mut Warnings) -> Result<Self> {
+        let columns = field_columns(&fields);
+        check_id(fields[0], warnings)?;
+        let parse_int = |i: usize| {
+            fields[i]
+                .parse::<i32>()
+                .map_err(|_| ParseError::InvalidInt {
+                    field: AA_FIELDS[i],
+                    value: fields[i].to_string(),
+                    column: columns[i],
+                })
+        };
+        let abbreviation_type =
+            AbbreviationType::from_str(fields[1]).map_err(|_| ParseError::InvalidValue {
+                field: "abbreviation_type",
+                value: fields[1].to_string(),
+                column: columns[1],
+            })?;
+        let short_token_count = parse_int(4)?;
+        let short_character_count = parse_int(5)?;
+        let long_token_count = parse_int(6)?;
+        let long_character_count = parse_int(7)?;
+        let source_positions = fields[8];
+        let position_parts = source_positions.split(':').collect::<Vec<&str>>();
+        check_parts(&position_parts).map_err(|_| ParseError::InvalidValue {
+            field: "positional_info",
+            value: source_positions.to_string(),
+            column: columns[8],
+        })?;
+        let parse_position = |part: &str| {
+            part.parse::<i32>().map_err(|_| ParseError::InvalidInt {
+                field: "positional_info",
+                value: part.to_string(),
+                column: columns[8] + offset_in(source_positions, part),
+            })
+        };
+        let pp1 = parse_position(position_parts[0])?;
+        let pp2 = parse_position(position_parts[1])?;
+        Ok(AaOutputRef {
+            id: fields[0],
+            abbreviation_type,
+            short_form: fields[2],
+            long_form: fields[3],
+            short_token_count,
+            short_character_count,
+            long_token_count,
+            long_character_count,
+            positional_info: AaPosInfo::new(pp1, pp2),
+        })
+    }
+
+    /// Converts into an owned [`AaOutput`].
+    pub fn into_owned(self) -> AaOutput {
+        AaOutput {
+            id: DocumentId::new_unchecked(self.id.to_string()),
+            abbreviation_type: self.abbreviation_type,
+            short_form: self.short_form.to_string(),
+            long_form: self.long_form.to_string(),
+            short_token_count: self.short_token_count,
+            short_character_count: self.short_character_count,
+            long_token_count: self.long_token_count,
+            long_character_count: self.long_character_count,
+            positional_info: self.positional_info,
+        }
+    }
+}
+
+/// Borrowed version of [`Output`].
+#[derive(Serialize, Debug, PartialEq)]
+pub enum OutputRef<'a> {
+    MMI(MmiOutputRef<'a>),
+    AA(AaOutputRef<'a>),
+}
+
+impl OutputRef<'_> {
+    /// Converts into an owned [`Output`].
+    pub fn into_owned(self) -> Output {
+        match self {
+            OutputRef::MMI(x) => Output::MMI(x.into_owned()),
+            OutputRef::AA(x) => Output::AA(x.into_owned()),
+        }
+    }
+}
+
+/// Zero-copy alternative to [`parse_record`](crate::parse_record).
+///
+/// Behaves exactly the same, including errors, but the returned record
+/// borrows from `text` instead of copying every field into a `String`.
+///
+/// ```rust
+/// let line = "23074487|AA|FY|fiscal years|1|2|3|12|9362:2";
+/// match mmi_parser::parse_record_ref(line).unwrap() {
+///     mmi_parser::OutputRef::AA(aa) => assert_eq!(aa.long_form, "fiscal years"),
+///     _ => unreachable!(),
+/// }
+/// ```
+pub fn parse_record_ref(text: &str) -> Result<OutputRef<'_>> {
+    parse_record_ref_with(text, &mut Warnings::new(Strictness::Lenient))
+}
+
+/// [`parse_record_ref`] with the identifier checks governed by `warnings`.
+pub(crate) fn parse_record_ref_with<'a>(
+    text: &'a str,
+    warnings: &mut Warnings,
+) -> Result<OutputRef<'a>> {
+    let parts = split_text(text);
+    match parts.get(1).map(|p| p.to_ascii_uppercase()).as_deref() {
+        Some("MMI") => {
+            let fields = <[&str; 10]>::try_from(parts.as_slice()).map_err(|_| {
+                log::debug!(
+                    "Record is not of the right length, expected 10 pipe-separated components, found {}",
+                    parts.len()
+                );
+                ParseError::FieldCount {
+                    expected: MMI_FIELDS.len(),
+                    found: parts.len(),
+                }
+            })?;
+            Ok(OutputRef::MMI(MmiOutputRef::from_fields(
+                fields,
+                Dialect::MetaMap,
+                warnings,
+            )?))
+        }
+        Some("AA") | Some("UA") => {
+            let fields =
+                <[&str; 9]>::try_from(parts.as_slice()).map_err(|_| ParseError::FieldCount {
+                    expected: AA_FIELDS.len(),
+                    found: parts.len(),
+                })?;
+            Ok(OutputRef::AA(AaOutputRef::from_fields(fields, warnings)?))
+        }
+        // only 2 valid length options, anything else is a malformed line
+        Some(_) if parts.len() == MMI_FIELDS.len() || parts.len() == AA_FIELDS.len() => {
+            Err(ParseError::InvalidValue {
+                field: "record_type",
+                value: parts[1].to_string(),
+                column: parts[0].len() + 1,
+            })
+        }
+        _ => Err(ParseError::FieldCount {
+            expected: MMI_FIELDS.len(),
+            found: parts.len(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_record;
+
+    #[test]
+    fn test_trigger_ref_borrows_unless_quotes_are_inside() {
+        let s1 = "24119710|MMI|170.37|Effect|C1280500|[qlco]|[\"effects\"-ti-1-\"Eff\"\"ects\"-noun-0]|TI|21/7|";
+        let parsed = match parse_record_ref(s1).unwrap() {
+            OutputRef::MMI(x) => x,
+            _ => panic!("expected MMI"),
+        };
+        assert_eq!(parsed.name, "Effect");
+        assert!(matches!(parsed.triggers[0].name, Cow::Borrowed("effects")));
+        assert!(matches!(
+            parsed.triggers[0].part_of_speech,
+            Cow::Borrowed("noun")
+        ));
+        assert_eq!(parsed.triggers[0].text, "Effects");
+        assert!(matches!(parsed.triggers[0].text, Cow::Owned(_)));
+    }
+
+    #[test]
+    fn test_ref_matches_owned() {
+        let samples = [
+            "3124119710|MMI|637.30|Isopoda|C0598806|[euka]|[\"Isopod\"-ab-1-\"isopod\"-adj-0,\"Isopoda\"-ti-1-\"Isopoda\"-noun-0]|TI;AB|228/6;136/7|B01.050.500.131.365.400",
+            "23074497|MMI|7.59|Transplant Recipients|C0376387|[podg]|[\"Transplant Recipients\"-ab-27-\"recipients transplants\"-noun-0]|AB|[4061/10,4075/11],[4166/10,4180/11]|M01.925",
+            "23074487|AA|FY|fiscal years|1|2|3|12|9362:2",
+            "23074487|AA|FY|fiscal years|1|2|3|12|9362",
+            "asda|fake|other stuff|",
+        ];
+        for sample in samples {
+            match (parse_record_ref(sample), parse_record(sample)) {
+                (Ok(borrowed), Ok(owned)) => {
+                    assert_eq!(
+                        serde_json::to_value(&borrowed).unwrap(),
+                        serde_json::to_value(&owned).unwrap()
+                    );
+                    assert_eq!(borrowed.into_owned(), owned);
+                }
+                (Err(e1), Err(e2)) => assert_eq!(e1, e2),
+                _ => panic!("borrowed and owned parsing disagree on {}", sample),
+            }
+        }
+    }
+}
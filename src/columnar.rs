@@ -0,0 +1,459 @@
+//! Conversion of parsed records into Apache Arrow [`RecordBatch`]es, available
+//! with the `parquet` feature.
+//!
+//! MMI and AA records have different shapes, so they go into two tables with
+//! the schemas of [`mmi_schema`] and [`aa_schema`]. Columns are named after the
+//! fields of [`MmiOutput`] and [`AaOutput`] (as in their json), and the nested
+//! parts keep their structure as list and struct columns:
+//!
+//! * `cui` is null for records with the [`Cui::missing`](crate::Cui::missing)
+//!   placeholder;
+//! * `semantic_types`, `location` and `tree_codes` are lists of strings, with
+//!   `tree_codes` being null when the record had none;
+//! * `triggers` is a list of structs with the fields of [`Trigger`], whose
+//!   `loc` is again a list of section tags;
+//! * `positional_info` is a struct holding the layout `case` and a list of
+//!   `occurrences`, each with its `group` index and list of `spans`.
+//!
+//! The batches can be handed to any Arrow consumer, e.g. the `parquet` crate's
+//! `ArrowWriter`, Polars or DuckDB.
+
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BooleanArray, Float64Array, Int32Array, ListArray, StringArray, StructArray,
+    UInt32Array,
+};
+use arrow::buffer::{NullBuffer, OffsetBuffer};
+use arrow::datatypes::{DataType, Field, FieldRef, Fields, Schema, SchemaRef};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+
+use crate::{AaOutput, Location, MmiOutput, Occurrence, Output, Section, Span, Trigger};
+
+fn item(data_type: DataType) -> FieldRef {
+    Arc::new(Field::new("item", data_type, false))
+}
+
+fn list_of(data_type: DataType) -> DataType {
+    DataType::List(item(data_type))
+}
+
+fn span_fields() -> Fields {
+    Fields::from(vec![
+        Field::new("start", DataType::Int32, false),
+        Field::new("length", DataType::Int32, false),
+    ])
+}
+
+fn occurrence_fields() -> Fields {
+    Fields::from(vec![
+        Field::new("group", DataType::UInt32, false),
+        Field::new("spans", list_of(DataType::Struct(span_fields())), false),
+    ])
+}
+
+fn positional_info_fields() -> Fields {
+    Fields::from(vec![
+        Field::new("case", DataType::Utf8, false),
+        Field::new(
+            "occurrences",
+            list_of(DataType::Struct(occurrence_fields())),
+            false,
+        ),
+    ])
+}
+
+fn trigger_fields() -> Fields {
+    Fields::from(vec![
+        Field::new("name", DataType::Utf8, false),
+        Field::new("loc", list_of(DataType::Utf8), false),
+        Field::new("loc_position", DataType::Int32, false),
+        Field::new("text", DataType::Utf8, false),
+        Field::new("part_of_speech", DataType::Utf8, false),
+        Field::new("negation", DataType::Boolean, false),
+    ])
+}
+
+/// Schema of the batches built from MMI records.
+pub fn mmi_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("mmi", DataType::Utf8, false),
+        Field::new("score", DataType::Float64, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("cui", DataType::Utf8, true),
+        Field::new("semantic_types", list_of(DataType::Utf8), false),
+        Field::new(
+            "triggers",
+            list_of(DataType::Struct(trigger_fields())),
+            false,
+        ),
+        Field::new("location", list_of(DataType::Utf8), false),
+        Field::new(
+            "positional_info",
+            DataType::Struct(positional_info_fields()),
+            false,
+        ),
+        Field::new("tree_codes", list_of(DataType::Utf8), true),
+    ]))
+}
+
+/// Schema of the batches built from AA records.
+pub fn aa_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("abbreviation_type", DataType::Utf8, false),
+        Field::new("short_form", DataType::Utf8, false),
+        Field::new("long_form", DataType::Utf8, false),
+        Field::new("short_token_count", DataType::Int32, false),
+        Field::new("short_character_count", DataType::Int32, false),
+        Field::new("long_token_count", DataType::Int32, false),
+        Field::new("long_character_count", DataType::Int32, false),
+        Field::new("positional_info", DataType::Struct(span_fields()), false),
+    ]))
+}
+
+fn strings<S: AsRef<str>>(values: impl IntoIterator<Item = S>) -> ArrayRef {
+    Arc::new(StringArray::from_iter_values(values))
+}
+
+fn ints(values: impl IntoIterator<Item = i32>) -> ArrayRef {
+    Arc::new(Int32Array::from_iter_values(values))
+}
+
+/// A list column whose rows take `lengths` consecutive items of `values`.
+fn list(
+    lengths: Vec<usize>,
+    values: ArrayRef,
+    nulls: Option<NullBuffer>,
+) -> Result<ArrayRef, ArrowError> {
+    let field = item(values.data_type().clone());
+    let offsets = OffsetBuffer::from_lengths(lengths);
+    Ok(Arc::new(ListArray::try_new(field, offsets, values, nulls)?))
+}
+
+fn structs(fields: Fields, columns: Vec<ArrayRef>) -> Result<ArrayRef, ArrowError> {
+    Ok(Arc::new(StructArray::try_new(fields, columns, None)?))
+}
+
+fn string_lists<'a, S: AsRef<str> + 'a>(
+    lists: impl IntoIterator<Item = &'a [S]>,
+) -> Result<ArrayRef, ArrowError> {
+    let mut lengths = Vec::new();
+    let mut values = Vec::new();
+    for items in lists {
+        lengths.push(items.len());
+        values.extend(items.iter().map(AsRef::as_ref));
+    }
+    list(lengths, strings(values), None)
+}
+
+fn locations<'a>(
+    locations: impl IntoIterator<Item = &'a Location>,
+) -> Result<ArrayRef, ArrowError> {
+    let sections = locations
+        .into_iter()
+        .map(|location| location.sections().iter().map(Section::as_str).collect())
+        .collect::<Vec<Vec<&str>>>();
+    string_lists(sections.iter().map(Vec::as_slice))
+}
+
+fn triggers(records: &[MmiOutput]) -> Result<ArrayRef, ArrowError> {
+    let triggers = records
+        .iter()
+        .flat_map(|r| &r.triggers)
+        .collect::<Vec<&Trigger>>();
+    let columns = vec![
+        strings(triggers.iter().map(|t| &t.name)),
+        locations(triggers.iter().map(|t| &t.loc))?,
+        ints(triggers.iter().map(|t| t.loc_position)),
+        strings(triggers.iter().map(|t| &t.text)),
+        strings(triggers.iter().map(|t| &t.part_of_speech)),
+        Arc::new(BooleanArray::from(
+            triggers.iter().map(|t| t.negation).collect::<Vec<_>>(),
+        )),
+    ];
+    let lengths = records.iter().map(|r| r.triggers.len()).collect();
+    list(lengths, structs(trigger_fields(), columns)?, None)
+}
+
+fn positional_info(records: &[MmiOutput]) -> Result<ArrayRef, ArrowError> {
+    let occurrences = records
+        .iter()
+        .flat_map(|r| &r.positional_info.occurrences)
+        .collect::<Vec<&Occurrence>>();
+    let spans = occurrences
+        .iter()
+        .flat_map(|o| &o.spans)
+        .collect::<Vec<&Span>>();
+    let spans = structs(
+        span_fields(),
+        vec![
+            ints(spans.iter().map(|s| s.start)),
+            ints(spans.iter().map(|s| s.length)),
+        ],
+    )?;
+    let groups = occurrences
+        .iter()
+        .map(|o| u32::try_from(o.group).unwrap_or(u32::MAX));
+    let occurrence_columns = vec![
+        Arc::new(UInt32Array::from_iter_values(groups)) as ArrayRef,
+        list(
+            occurrences.iter().map(|o| o.spans.len()).collect(),
+            spans,
+            None,
+        )?,
+    ];
+    let occurrences = structs(occurrence_fields(), occurrence_columns)?;
+    let lengths = records
+        .iter()
+        .map(|r| r.positional_info.occurrences.len())
+        .collect();
+    structs(
+        positional_info_fields(),
+        vec![
+            strings(
+                records
+                    .iter()
+                    .map(|r| format!("{:?}", r.positional_info.case)),
+            ),
+            list(lengths, occurrences, None)?,
+        ],
+    )
+}
+
+fn tree_codes(records: &[MmiOutput]) -> Result<ArrayRef, ArrowError> {
+    let mut lengths = Vec::new();
+    let mut values = Vec::new();
+    for record in records {
+        let codes = record.tree_codes.as_deref().unwrap_or_default();
+        lengths.push(codes.len());
+        values.extend(codes.iter().map(|code| code.as_str()));
+    }
+    let valid = records
+        .iter()
+        .map(|r| r.tree_codes.is_some())
+        .collect::<Vec<_>>();
+    list(lengths, strings(values), Some(NullBuffer::from(valid)))
+}
+
+/// Builds a batch with the [`mmi_schema`] holding `records`, in order.
+pub fn mmi_batch(records: &[MmiOutput]) -> Result<RecordBatch, ArrowError> {
+    let columns = vec![
+        strings(records.iter().map(|r| r.id.as_str())),
+        strings(records.iter().map(|r| &r.mmi)),
+        Arc::new(Float64Array::from_iter_values(
+            records.iter().map(|r| r.score),
+        )) as ArrayRef,
+        strings(records.iter().map(|r| &r.name)),
+        Arc::new(StringArray::from_iter(
+            records
+                .iter()
+                .map(|r| Some(r.cui.as_str()).filter(|_| !r.cui.is_missing())),
+        )) as ArrayRef,
+        string_lists(records.iter().map(|r| r.semantic_types.as_slice()))?,
+        triggers(records)?,
+        locations(records.iter().map(|r| &r.location))?,
+        positional_info(records)?,
+        tree_codes(records)?,
+    ];
+    RecordBatch::try_new(mmi_schema(), columns)
+}
+
+/// Builds a batch with the [`aa_schema`] holding `records`, in order.
+pub fn aa_batch(records: &[AaOutput]) -> Result<RecordBatch, ArrowError> {
+    let positions = structs(
+        span_fields(),
+        vec![
+            ints(records.iter().map(|r| r.positional_info.start)),
+            ints(records.iter().map(|r| r.positional_info.length)),
+        ],
+    )?;
+    let columns = vec![
+        strings(records.iter().map(|r| r.id.as_str())),
+        strings(records.iter().map(|r| r.abbreviation_type.to_string())),
+        strings(records.iter().map(|r| &r.short_form)),
+        strings(records.iter().map(|r| &r.long_form)),
+        ints(records.iter().map(|r| r.short_token_count)),
+        ints(records.iter().map(|r| r.short_character_count)),
+        ints(records.iter().map(|r| r.long_token_count)),
+        ints(records.iter().map(|r| r.long_character_count)),
+        positions,
+    ];
+    RecordBatch::try_new(aa_schema(), columns)
+}
+
+/// The MMI and AA batches built from a set of records.
+#[derive(Debug, Clone)]
+pub struct Batches {
+    pub mmi: RecordBatch,
+    pub aa: RecordBatch,
+}
+
+/// Collects records and turns them into batches, so large inputs can be
+/// written a batch at a time.
+///
+/// ```rust
+/// use mmi_parser::columnar::BatchBuilder;
+///
+/// let mut builder = BatchBuilder::new();
+/// builder.push(mmi_parser::parse_record("23074487|AA|FY|fiscal years|1|2|3|12|9362:2").unwrap());
+/// assert_eq!(builder.aa_len(), 1);
+/// let batch = builder.finish_aa().unwrap();
+/// assert_eq!(batch.num_rows(), 1);
+/// assert_eq!(builder.aa_len(), 0);
+/// ```
+#[derive(Debug, Default)]
+pub struct BatchBuilder {
+    mmi: Vec<MmiOutput>,
+    aa: Vec<AaOutput>,
+}
+
+impl BatchBuilder {
+    /// Creates a builder without any records.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a record to the batch of its type.
+    pub fn push(&mut self, output: Output) {
+        match output {
+            Output::MMI(x) => self.mmi.push(x),
+            Output::AA(x) => self.aa.push(x),
+        }
+    }
+
+    /// Number of MMI records waiting to be turned into a batch.
+    pub fn mmi_len(&self) -> usize {
+        self.mmi.len()
+    }
+
+    /// Number of AA records waiting to be turned into a batch.
+    pub fn aa_len(&self) -> usize {
+        self.aa.len()
+    }
+
+    /// Builds a batch of the MMI records pushed so far, removing them from
+    /// the builder.
+    pub fn finish_mmi(&mut self) -> Result<RecordBatch, ArrowError> {
+        let batch = mmi_batch(&self.mmi);
+        self.mmi.clear();
+        batch
+    }
+
+    /// Builds a batch of the AA records pushed so far, removing them from
+    /// the builder.
+    pub fn finish_aa(&mut self) -> Result<RecordBatch, ArrowError> {
+        let batch = aa_batch(&self.aa);
+        self.aa.clear();
+        batch
+    }
+}
+
+/// Builds the MMI and AA batches of all records of `outputs`.
+pub fn record_batches(outputs: impl IntoIterator<Item = Output>) -> Result<Batches, ArrowError> {
+    let mut builder = BatchBuilder::new();
+    for output in outputs {
+        builder.push(output);
+    }
+    Ok(Batches {
+        mmi: builder.finish_mmi()?,
+        aa: builder.finish_aa()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_record;
+    use arrow::array::{Array, AsArray};
+
+    const LINES: [&str; 3] = [
+        "24119710|MMI|637.30|Isopoda|C0598806|[euka]|[\"Isopod\"-ab-1-\"isopod\"-adj-0,\"Isopoda\"-ti-1-\"Isopoda\"-noun-0]|TI;AB|228/6;136/7|B01.050.500.131.365.400",
+        "24119710|MMI|5.18|Heart|C0018787|[bpoc]|[\"heart\"-ab-1-\"heart\"-noun-1]|AB|[11/5],[40/5,50/3]|",
+        "23074487|AA|FY|fiscal years|1|2|3|12|9362:2",
+    ];
+
+    #[test]
+    fn test_record_batches() {
+        let outputs = LINES.iter().map(|line| parse_record(line).unwrap());
+        let batches = record_batches(outputs).unwrap();
+        assert_eq!(batches.mmi.schema(), mmi_schema());
+        assert_eq!(batches.mmi.num_rows(), 2);
+        assert_eq!(batches.aa.num_rows(), 1);
+
+        let triggers = batches
+            .mmi
+            .column_by_name("triggers")
+            .unwrap()
+            .as_list::<i32>();
+        assert_eq!(triggers.value_length(0), 2);
+        let second = triggers.value(1);
+        let second = second.as_struct();
+        assert!(second
+            .column_by_name("negation")
+            .unwrap()
+            .as_boolean()
+            .value(0));
+
+        let tree_codes = batches.mmi.column_by_name("tree_codes").unwrap();
+        assert!(tree_codes.is_valid(0) && tree_codes.is_null(1));
+
+        let positional_info = batches
+            .mmi
+            .column_by_name("positional_info")
+            .unwrap()
+            .as_struct();
+        let cases = positional_info
+            .column_by_name("case")
+            .unwrap()
+            .as_string::<i32>();
+        assert_eq!(cases.value(1), "D");
+        let occurrences = positional_info
+            .column_by_name("occurrences")
+            .unwrap()
+            .as_list::<i32>();
+        assert_eq!(occurrences.value_length(1), 2);
+        let spans = occurrences.value(1);
+        let spans = spans
+            .as_struct()
+            .column_by_name("spans")
+            .unwrap()
+            .as_list::<i32>();
+        assert_eq!(spans.value_length(1), 2);
+
+        let short_forms = batches
+            .aa
+            .column_by_name("short_form")
+            .unwrap()
+            .as_string::<i32>();
+        assert_eq!(short_forms.value(0), "FY");
+    }
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let outputs = LINES.iter().map(|line| parse_record(line).unwrap());
+        let batch = record_batches(outputs).unwrap().mmi;
+        let path = std::env::temp_dir().join("mmi_parser_columnar_test.parquet");
+        let file = std::fs::File::create(&path).unwrap();
+        let mut writer = parquet::arrow::ArrowWriter::try_new(file, mmi_schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(batches, [batch]);
+    }
+
+    #[test]
+    fn test_empty_batches() {
+        let batches = record_batches(Vec::new()).unwrap();
+        assert_eq!(batches.mmi.num_rows(), 0);
+        assert_eq!(batches.aa.schema(), aa_schema());
+    }
+}
@@ -0,0 +1,164 @@
+//! Grouping of records into the documents they were produced for.
+//!
+//! MetaMap writes one concept (or abbreviation) per line, with every line of a
+//! document sharing the same `id`. [`DocumentReader`] collects consecutive lines
+//! with the same id back into a single [`Document`].
+
+use serde::{Deserialize, Serialize};
+use std::io::BufRead;
+
+use crate::{AaOutput, DocumentId, MmiOutput, MmiReader, Output, ReadError};
+
+/// All the records of a single document.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    /// id shared by all of the document's records
+    pub id: DocumentId,
+    /// MMI records, in the order they appeared
+    pub mmi: Vec<MmiOutput>,
+    /// AA/UA records, in the order they appeared
+    pub aa: Vec<AaOutput>,
+}
+
+impl Document {
+    /// Creates a document without any records.
+    pub fn new(id: DocumentId) -> Self {
+        Document {
+            id,
+            mmi: Vec::new(),
+            aa: Vec::new(),
+        }
+    }
+
+    /// Adds a record to the document, regardless of its id.
+    pub fn push(&mut self, output: Output) {
+        match output {
+            Output::MMI(x) => self.mmi.push(x),
+            Output::AA(x) => self.aa.push(x),
+        }
+    }
+
+    /// Total number of records in the document.
+    pub fn len(&self) -> usize {
+        self.mmi.len() + self.aa.len()
+    }
+
+    /// Whether the document has no records.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Iterator over the [`Document`]s of an [`MmiReader`].
+///
+/// Consecutive records with the same id form one document; a document is
+/// yielded once a record with a different id (or the end of the input) is
+/// reached. Errors from the line reader are passed through as they happen, so
+/// they can come before the document the bad line belonged to. Warnings of a
+/// [lenient](crate::Strictness::Lenient) reader are logged.
+///
+/// ```rust
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let file = File::open("data/MMI_sample.txt").unwrap();
+/// let lines = mmi_parser::MmiReader::new(BufReader::new(file));
+/// for document in mmi_parser::DocumentReader::new(lines) {
+///     let document = document.unwrap();
+///     println!("{}: {} concepts", document.id, document.mmi.len());
+/// }
+/// ```
+pub struct DocumentReader<R> {
+    lines: MmiReader<R>,
+    pending: Option<Document>,
+}
+
+impl<R: BufRead> DocumentReader<R> {
+    /// Creates a new reader grouping the records of `lines`.
+    pub fn new(lines: MmiReader<R>) -> Self {
+        DocumentReader {
+            lines,
+            pending: None,
+        }
+    }
+
+    /// Consumes the `DocumentReader`, returning the underlying line reader.
+    ///
+    /// Any partially collected document is discarded.
+    pub fn into_inner(self) -> MmiReader<R> {
+        self.lines
+    }
+}
+
+impl<R: BufRead> Iterator for DocumentReader<R> {
+    type Item = Result<Document, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let parsed = match self.lines.next() {
+                None => return self.pending.take().map(Ok),
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(parsed)) => parsed,
+            };
+            for warning in &parsed.warnings {
+                log::warn!("line {}: {}", parsed.line_number, warning);
+            }
+            match &mut self.pending {
+                Some(document) if &document.id == parsed.output.id() => {
+                    document.push(parsed.output);
+                }
+                _ => {
+                    let mut document = Document::new(parsed.output.id().clone());
+                    document.push(parsed.output);
+                    if let Some(finished) = self.pending.replace(document) {
+                        return Some(Ok(finished));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_document_reader_groups_consecutive_ids() {
+        let input = [
+            "1|MMI|170.37|Effect|C1280500|[qlco]|[\"effects\"-ti-1-\"Effects\"-noun-0]|TI|21/7|",
+            "1|AA|FY|fiscal years|1|2|3|12|9362:2",
+            "1|MMI|5.18|Carcinoma of lung|C0684249|[neop]|[\"LUNG CANCER\"-tx-1-\"lung cancer\"-noun-0]|TX|0/11|",
+            "not a record",
+            "2|MMI|170.37|Effect|C1280500|[qlco]|[\"effects\"-ti-1-\"Effects\"-noun-0]|TI|21/7|",
+            "1|AA|FY|fiscal years|1|2|3|12|9362:2",
+        ]
+        .join("\n");
+        let items = DocumentReader::new(MmiReader::new(Cursor::new(input))).collect::<Vec<_>>();
+        assert_eq!(items.len(), 4);
+        assert!(matches!(
+            items[0],
+            Err(ReadError::Parse { line_number: 4, .. })
+        ));
+        let documents = items[1..]
+            .iter()
+            .map(|item| item.as_ref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(documents[0].id.as_str(), "1");
+        assert_eq!(documents[0].mmi.len(), 2);
+        assert_eq!(documents[0].aa.len(), 1);
+        assert_eq!(documents[1].id.as_str(), "2");
+        assert_eq!(documents[1].len(), 1);
+        // ids are only grouped while they are consecutive
+        assert_eq!(documents[2].id.as_str(), "1");
+        assert_eq!(documents[2].aa.len(), 1);
+    }
+
+    #[test]
+    fn test_document_reader_empty_input() {
+        let lines = MmiReader::new(Cursor::new(""));
+        assert_eq!(DocumentReader::new(lines).count(), 0);
+    }
+}
@@ -0,0 +1,514 @@
+//! Parser for MetaMap's default, human-readable output.
+//!
+//! ```text
+//! Processing 24119710.ti.1: Effects of heart attack
+//!
+//! Phrase: of heart attack
+//! Meta Candidates (2):
+//!   1000   Heart attack (Myocardial Infarction) [Disease or Syndrome]
+//!    861   Heart [Body Part, Organ, or Organ Component]
+//! Meta Mapping (1000):
+//!   1000   Heart attack (Myocardial Infarction) [Disease or Syndrome]
+//! ```
+//!
+//! MetaMap only prints the CUI of each concept (`1000   C0027051:Heart attack
+//! ...`) when run with `-I` (`--show_cuis`), and concept lines are read with or
+//! without it. The format is meant for reading rather than parsing, so
+//! [`HumanReader`] is tolerant: anything it does not recognize (banners,
+//! `>>>>>` markers and the like) is skipped. Semantic types are
+//! written out in full and are turned back into their abbreviations with the
+//! built-in [table](crate::SemanticType) where possible. The output holds no
+//! positions, sources or syntactic information, so neither do the parsed types.
+
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+use crate::aggregate::{aggregate, ConceptMatch};
+use crate::machine::split_utterance_id;
+use crate::{MmiOutput, Section, SemanticType};
+
+/// A concept as listed under `Meta Candidates` or `Meta Mapping`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candidate {
+    /// score as printed, 1000 being a perfect match
+    pub score: i32,
+    /// only printed when MetaMap was run with `-I` (`--show_cuis`)
+    pub cui: Option<String>,
+    /// the concept string that matched
+    pub matched: String,
+    /// same as `matched` when MetaMap did not print a separate preferred name
+    pub preferred_name: String,
+    /// semantic type abbreviations, or the names as written for unknown types
+    pub semantic_types: Vec<String>,
+    /// whether the concept was marked negated (`N`)
+    pub negated: bool,
+}
+
+/// One of the best combinations of candidates covering a phrase.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mapping {
+    /// score as printed, 1000 being a perfect mapping
+    pub score: i32,
+    pub candidates: Vec<Candidate>,
+}
+
+/// A phrase of an utterance along with what MetaMap found for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Phrase {
+    pub text: String,
+    pub candidates: Vec<Candidate>,
+    pub mappings: Vec<Mapping>,
+}
+
+/// A sentence (or title) of a document, started by a `Processing` line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Utterance {
+    /// `<document id>.<section>.<number>`, e.g. `24119710.ti.1`
+    pub id: String,
+    pub text: String,
+    pub phrases: Vec<Phrase>,
+}
+
+impl Utterance {
+    /// Id of the document the utterance belongs to (the whole id if it has no section).
+    pub fn document_id(&self) -> &str {
+        split_utterance_id(&self.id).map_or(&self.id, |(document, ..)| document)
+    }
+
+    /// Section the utterance was found in, e.g. `TI`.
+    pub fn section(&self) -> Option<Section> {
+        split_utterance_id(&self.id).and_then(|(_, section, _)| Section::from_str(section).ok())
+    }
+
+    /// Number of the utterance within its section (starting with 1).
+    pub fn number(&self) -> Option<i32> {
+        split_utterance_id(&self.id).map(|(.., number)| number)
+    }
+
+    /// The last phrase, adding an unnamed one for concepts listed before any
+    /// `Phrase:` line.
+    fn last_phrase(&mut self) -> &mut Phrase {
+        if self.phrases.is_empty() {
+            self.phrases.push(Phrase {
+                text: String::new(),
+                candidates: Vec::new(),
+                mappings: Vec::new(),
+            });
+        }
+        self.phrases.last_mut().expect("a phrase was just added")
+    }
+}
+
+/// Splits `matched (preferred)` into its two names, if the last parenthesized
+/// group is not the whole text.
+fn split_preferred(names: &str) -> (&str, &str) {
+    if let Some(inner) = names.strip_suffix(')') {
+        let mut depth = 0;
+        for (i, c) in inner.char_indices().rev() {
+            match c {
+                ')' => depth += 1,
+                '(' if depth > 0 => depth -= 1,
+                '(' if i > 0 => return (inner[..i].trim_end(), &inner[i + 1..]),
+                '(' => break,
+                _ => {}
+            }
+        }
+    }
+    (names, names)
+}
+
+/// Turns a list of semantic type names back into abbreviations.
+///
+/// Some names contain commas themselves (`Body Part, Organ, or Organ
+/// Component`), so pieces are joined until they end in a known name. Pieces
+/// that never become part of one are kept as written.
+fn semantic_types(list: &str) -> Vec<String> {
+    let lookup = |name: &str| {
+        let name = name.trim();
+        SemanticType::from_name(name).or_else(|| SemanticType::from_abbreviation(name))
+    };
+    let unknown = |pieces: &[&str]| {
+        pieces
+            .iter()
+            .map(|piece| piece.trim())
+            .filter(|piece| !piece.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    let mut types = Vec::new();
+    let mut pending: Vec<&str> = Vec::new();
+    for piece in list.split(',') {
+        pending.push(piece);
+        let found = (0..pending.len())
+            .find_map(|start| lookup(&pending[start..].join(",")).map(|st| (start, st)));
+        if let Some((start, st)) = found {
+            types.extend(unknown(&pending[..start]));
+            types.push(st.abbreviation.to_string());
+            pending.clear();
+        }
+    }
+    types.extend(unknown(&pending));
+    types
+}
+
+/// Parses a concept line such as
+/// `1000 C0027051:Heart attack (Myocardial Infarction) [Disease or Syndrome]`,
+/// or the same without `C0027051:` when MetaMap did not print CUIs, returning
+/// `None` if the line does not have that shape.
+///
+/// Lines without a CUI must end in their semantic types, so that other lines
+/// starting with a number are not taken for concepts.
+pub fn parse_candidate(line: &str) -> Option<Candidate> {
+    let (score, rest) = line.trim().split_once(char::is_whitespace)?;
+    let score = score.parse::<i32>().ok()?;
+    let mut rest = rest.trim_start();
+    let negated = match rest.strip_prefix("N ") {
+        Some(after) => {
+            rest = after.trim_start();
+            true
+        }
+        None => false,
+    };
+    let (cui, rest) = match rest.split_once(':') {
+        Some((cui, after)) if looks_like_cui(cui) => (Some(cui.to_string()), after),
+        _ => (None, rest),
+    };
+    let (names, types) = match rest.rfind('[') {
+        Some(open) => {
+            let close = open + rest[open..].find(']')?;
+            (rest[..open].trim(), semantic_types(&rest[open + 1..close]))
+        }
+        None if cui.is_some() => (rest.trim(), Vec::new()),
+        None => return None,
+    };
+    if names.is_empty() {
+        return None;
+    }
+    let (matched, preferred_name) = split_preferred(names);
+    Some(Candidate {
+        score,
+        cui,
+        matched: matched.to_string(),
+        preferred_name: preferred_name.to_string(),
+        semantic_types: types,
+        negated,
+    })
+}
+
+/// Whether `word` is a CUI such as `C0027051`.
+fn looks_like_cui(word: &str) -> bool {
+    match word.strip_prefix('C') {
+        Some(digits) => !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// Whether `text` (the start of an input) looks like human-readable output,
+/// which may start with a banner before the first `Processing` line.
+pub fn looks_like_human_output(text: &str) -> bool {
+    text.lines().any(|line| line.starts_with("Processing "))
+}
+
+/// What concept lines are currently listed under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Listing {
+    Nothing,
+    Candidates,
+    Mapping,
+}
+
+/// Iterator over the [`Utterance`]s of human-readable output read from any
+/// [`BufRead`].
+///
+/// Each utterance is yielded once the next `Processing` line (or the end of the
+/// input) is reached. Lines before the first utterance are skipped, and the only
+/// errors are those of the underlying reader.
+pub struct HumanReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    current: Option<Utterance>,
+    listing: Listing,
+    finished: bool,
+}
+
+impl<R: BufRead> HumanReader<R> {
+    /// Creates a new reader over `reader`.
+    pub fn new(reader: R) -> Self {
+        HumanReader {
+            reader,
+            buffer: Vec::new(),
+            current: None,
+            listing: Listing::Nothing,
+            finished: false,
+        }
+    }
+
+    /// Adds a line to the current utterance, returning the previous utterance
+    /// when the line starts a new one.
+    fn add_line(&mut self, line: &str) -> Option<Utterance> {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("Processing ") {
+            let (id, text) = rest
+                .split_once(": ")
+                .unwrap_or((rest.trim_end_matches(':'), ""));
+            self.listing = Listing::Nothing;
+            return self.current.replace(Utterance {
+                id: id.trim().to_string(),
+                text: text.trim().to_string(),
+                phrases: Vec::new(),
+            });
+        }
+        let utterance = self.current.as_mut()?;
+        if let Some(text) = line.strip_prefix("Phrase:") {
+            utterance.phrases.push(Phrase {
+                text: text.trim().trim_matches('"').to_string(),
+                candidates: Vec::new(),
+                mappings: Vec::new(),
+            });
+            self.listing = Listing::Nothing;
+        } else if line.starts_with("Meta Candidates") {
+            self.listing = Listing::Candidates;
+        } else if let Some(rest) = line.strip_prefix("Meta Mapping") {
+            let score = rest
+                .trim()
+                .trim_start_matches('(')
+                .split(')')
+                .next()
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or_default();
+            utterance.last_phrase().mappings.push(Mapping {
+                score,
+                candidates: Vec::new(),
+            });
+            self.listing = Listing::Mapping;
+        } else if let Some(candidate) = parse_candidate(line) {
+            let phrase = utterance.last_phrase();
+            match self.listing {
+                Listing::Candidates => phrase.candidates.push(candidate),
+                Listing::Mapping => {
+                    if let Some(mapping) = phrase.mappings.last_mut() {
+                        mapping.candidates.push(candidate);
+                    }
+                }
+                Listing::Nothing => log::debug!("Skipping concept outside of a listing: {}", line),
+            }
+        } else if !line.is_empty() {
+            log::debug!("Skipping line: {}", line);
+        }
+        None
+    }
+}
+
+impl<R: BufRead> Iterator for HumanReader<R> {
+    type Item = io::Result<Utterance>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            self.buffer.clear();
+            match self.reader.read_until(b'\n', &mut self.buffer) {
+                Ok(0) => self.finished = true,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&self.buffer).into_owned();
+                    if let Some(utterance) = self.add_line(&line) {
+                        return Some(Ok(utterance));
+                    }
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.current.take().map(Ok)
+    }
+}
+
+/// Projects the utterances of one document onto MMI-style concept records.
+///
+/// Every candidate of every mapping becomes a trigger of the record for its
+/// CUI, with the score being the best mapping score. Concepts printed without
+/// a CUI (MetaMap was not run with `-I`) are kept, one record per preferred
+/// name, with the [`Cui::missing`](crate::Cui::missing) placeholder. As the
+/// format has no positions or parts of speech, the positional information of
+/// the records is empty, triggers have no part of speech, and the text of a
+/// trigger is the whole phrase. The document id is taken from the first
+/// utterance.
+///
+/// The records are written back as MMI lines with empty `cui` and
+/// `positional_info` fields when there is no CUI; such lines only parse back
+/// with [`Strictness::Lenient`](crate::Strictness::Lenient).
+pub fn to_mmi_outputs(utterances: &[Utterance]) -> Vec<MmiOutput> {
+    let id = utterances.first().map_or("", Utterance::document_id);
+    let mut matches = Vec::new();
+    for utterance in utterances {
+        let section = utterance.section().unwrap_or(Section::TX);
+        for phrase in &utterance.phrases {
+            for candidate in phrase.mappings.iter().flat_map(|m| &m.candidates) {
+                matches.push(ConceptMatch {
+                    cui: candidate.cui.clone(),
+                    preferred_name: candidate.preferred_name.clone(),
+                    matched: candidate.matched.clone(),
+                    semantic_types: candidate.semantic_types.clone(),
+                    score: f64::from(candidate.score.abs()),
+                    section: section.clone(),
+                    utterance_number: utterance.number().unwrap_or(1),
+                    text: phrase.text.clone(),
+                    part_of_speech: String::new(),
+                    negated: candidate.negated,
+                    spans: Vec::new(),
+                });
+            }
+        }
+    }
+    aggregate(id, matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "/opt/public_mm/bin/SKRrun.16 -L 2016 -Z 2016AA
+Berkeley DB databases (USAbase 2016AA strict model) are open.
+
+Processing 24119710.ti.1: Effects of heart attack
+
+Phrase: Effects
+Meta Candidates (1):
+  1000   C1280500:Effects (Effect) [Qualitative Concept]
+Meta Mapping (1000):
+  1000   C1280500:Effects (Effect) [Qualitative Concept]
+
+Phrase: of heart attack
+>>>>> Phrase
+of heart attack
+<<<<< Phrase
+Meta Candidates (2):
+  1000   C0027051:Heart attack (Myocardial Infarction) [Disease or Syndrome]
+   861   C0018787:Heart [Body Part, Organ, or Organ Component]
+Meta Mapping (1000):
+  1000   C0027051:Heart attack (Myocardial Infarction) [Disease or Syndrome]
+Processing 24119710.ab.1: No heart attack.
+
+Phrase: No heart attack.
+Meta Mapping (861):
+   861 N C0027051:Heart attack (Myocardial Infarction) [Disease or Syndrome]
+";
+
+    #[test]
+    fn test_parse_candidate() {
+        let candidate =
+            parse_candidate("   861   C0018787:Heart [Body Part, Organ, or Organ Component]")
+                .unwrap();
+        assert_eq!(candidate.score, 861);
+        assert_eq!(candidate.cui.as_deref(), Some("C0018787"));
+        assert_eq!(candidate.matched, "Heart");
+        assert_eq!(candidate.preferred_name, "Heart");
+        assert_eq!(candidate.semantic_types, ["bpoc"]);
+        let candidate = parse_candidate(
+            "1000 N C0006826:Cancer (Malignant (neoplasm)) [Neoplastic Process, Unknown Type,Finding] {MSH}",
+        )
+        .unwrap();
+        assert!(candidate.negated);
+        assert_eq!(candidate.matched, "Cancer");
+        assert_eq!(candidate.preferred_name, "Malignant (neoplasm)");
+        assert_eq!(candidate.semantic_types, ["neop", "Unknown Type", "fndg"]);
+        assert_eq!(parse_candidate("Meta Candidates (2):"), None);
+        assert_eq!(parse_candidate("1000 no cui here"), None);
+    }
+
+    #[test]
+    fn test_parse_candidate_without_cui() {
+        let candidate =
+            parse_candidate("  1000   Heart attack (Myocardial Infarction) [Disease or Syndrome]")
+                .unwrap();
+        assert_eq!(candidate.cui, None);
+        assert_eq!(candidate.matched, "Heart attack");
+        assert_eq!(candidate.preferred_name, "Myocardial Infarction");
+        assert_eq!(candidate.semantic_types, ["dsyn"]);
+        let candidate = parse_candidate("861 N Ratio: 1 [Quantitative Concept]").unwrap();
+        assert!(candidate.negated);
+        assert_eq!(candidate.cui, None);
+        assert_eq!(candidate.matched, "Ratio: 1");
+
+        let input = "Processing 24119710.ti.1: Effects of heart attack
+
+Phrase: of heart attack
+Meta Mapping (1000):
+  1000   Heart attack (Myocardial Infarction) [Disease or Syndrome]
+Phrase: of heart
+Meta Mapping (861):
+   861   Heart [Body Part, Organ, or Organ Component]
+";
+        let utterances = HumanReader::new(input.as_bytes())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(utterances[0].phrases[1].mappings[0].candidates.len(), 1);
+        let records = to_mmi_outputs(&utterances);
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.cui.is_missing()));
+        assert_eq!(records[0].name, "Myocardial Infarction");
+        assert_eq!(records[1].name, "Heart");
+        assert_eq!(records[1].score, 861.0);
+
+        // the records only round-trip through lenient parsing
+        let line = records[1].to_mmi_line();
+        assert_eq!(
+            line,
+            "24119710|MMI|861.00|Heart||[bpoc]|[\"Heart\"-ti-1-\"of heart\"--0]|TI||"
+        );
+        assert!(crate::parse_record_with(&line, crate::Strictness::Strict).is_err());
+        let (parsed, warnings) =
+            crate::parse_record_with(&line, crate::Strictness::Lenient).unwrap();
+        assert_eq!(warnings.len(), 1);
+        match parsed {
+            crate::Output::MMI(parsed) => assert_eq!(parsed, records[1]),
+            _ => panic!("expected an MMI record"),
+        }
+    }
+
+    #[test]
+    fn test_human_reader() {
+        let utterances = HumanReader::new(SAMPLE.as_bytes())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(utterances.len(), 2);
+        let first = &utterances[0];
+        assert_eq!(first.document_id(), "24119710");
+        assert_eq!(first.section(), Some(Section::TI));
+        assert_eq!(first.text, "Effects of heart attack");
+        assert_eq!(first.phrases.len(), 2);
+        let phrase = &first.phrases[1];
+        assert_eq!(phrase.text, "of heart attack");
+        assert_eq!(phrase.candidates.len(), 2);
+        assert_eq!(phrase.mappings[0].score, 1000);
+        assert_eq!(
+            phrase.mappings[0].candidates[0].preferred_name,
+            "Myocardial Infarction"
+        );
+        assert!(utterances[1].phrases[0].mappings[0].candidates[0].negated);
+        assert!(looks_like_human_output(SAMPLE));
+        assert!(!looks_like_human_output("1|MMI|..."));
+    }
+
+    #[test]
+    fn test_human_to_mmi_outputs() {
+        let utterances = HumanReader::new(SAMPLE.as_bytes())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        let records = to_mmi_outputs(&utterances);
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].to_string(),
+            "24119710|MMI|1000.00|Effect|C1280500|[qlco]|[\"Effects\"-ti-1-\"Effects\"--0]|TI||"
+        );
+        assert_eq!(
+            crate::parse_record(&records[0].to_string()).unwrap(),
+            crate::Output::MMI(to_mmi_outputs(&utterances).remove(0))
+        );
+        let heart = &records[1];
+        assert_eq!(heart.location.to_string(), "TI;AB");
+        assert_eq!(heart.triggers.len(), 2);
+        assert!(heart.triggers[1].negation);
+    }
+}
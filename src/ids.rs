@@ -0,0 +1,186 @@
+//! Identifier newtypes for the `id` and `cui` fields of MMI/AA records.
+//!
+//! Both are only enforced when parsing with [`Strictness::Strict`](crate::Strictness::Strict).
+//! Otherwise malformed values are still kept (and reported as warnings), so the types can
+//! hold values that would not pass [`FromStr`]; `is_valid` tells them apart.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::ValueError;
+
+/// A UMLS Concept Unique Identifier: `C` followed by 7 digits, e.g. `C0598806`.
+///
+/// Ordering and equality follow the identifier text, which for valid CUIs is
+/// the same as ordering by number.
+///
+/// Deserialization accepts any string so that records parsed leniently can be
+/// read back; use [`Cui::is_valid`] to check.
+///
+/// ```rust
+/// use mmi_parser::Cui;
+///
+/// let cui: Cui = "C0598806".parse().unwrap();
+/// assert_eq!(cui.number(), Some(598806));
+/// assert!("C05988".parse::<Cui>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Cui(String);
+
+impl Cui {
+    /// Whether `cui` has the `C` + 7 digit form.
+    pub(crate) fn is_valid_str(cui: &str) -> bool {
+        cui.len() == 8 && cui.starts_with('C') && cui.bytes().skip(1).all(|b| b.is_ascii_digit())
+    }
+
+    /// Wraps a value without validating it, used by lenient parsing.
+    pub(crate) fn new_unchecked(cui: String) -> Cui {
+        Cui(cui)
+    }
+
+    /// Placeholder for concepts MetaMap reported without a CUI, such as those of
+    /// human-readable output printed without `-I`. It is empty, so it is never
+    /// valid and is written as an empty field (or `NULL` by the exports).
+    pub fn missing() -> Cui {
+        Cui(String::new())
+    }
+
+    /// Whether this is the [`Cui::missing`] placeholder.
+    pub fn is_missing(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The identifier as written in MMI output.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether the identifier has the `C` + 7 digit form.
+    pub fn is_valid(&self) -> bool {
+        Cui::is_valid_str(&self.0)
+    }
+
+    /// The numeric part of a valid identifier.
+    pub fn number(&self) -> Option<u32> {
+        if self.is_valid() {
+            self.0[1..].parse().ok()
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for Cui {
+    type Err = ValueError;
+    /// Parses a CUI, erroring if it is not `C` followed by 7 digits.
+    fn from_str(s: &str) -> Result<Cui, ValueError> {
+        if Cui::is_valid_str(s) {
+            Ok(Cui(s.to_string()))
+        } else {
+            log::debug!("Unexpected CUI: {}", s);
+            Err(ValueError)
+        }
+    }
+}
+
+impl Display for Cui {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Identifier of the document a record was produced for (the first field).
+///
+/// MetaMap copies this from the input (a PMID, `USER`, ...), so the only
+/// requirement is that it is not empty.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DocumentId(String);
+
+impl DocumentId {
+    /// Whether `id` is acceptable as a document id.
+    pub(crate) fn is_valid_str(id: &str) -> bool {
+        !id.trim().is_empty()
+    }
+
+    /// Wraps a value without validating it, used by lenient parsing.
+    pub(crate) fn new_unchecked(id: String) -> DocumentId {
+        DocumentId(id)
+    }
+
+    /// The identifier as written in MMI output.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether the identifier is not blank.
+    pub fn is_valid(&self) -> bool {
+        DocumentId::is_valid_str(&self.0)
+    }
+}
+
+impl FromStr for DocumentId {
+    type Err = ValueError;
+    /// Parses a document id, erroring if it is blank.
+    fn from_str(s: &str) -> Result<DocumentId, ValueError> {
+        if DocumentId::is_valid_str(s) {
+            Ok(DocumentId(s.to_string()))
+        } else {
+            Err(ValueError)
+        }
+    }
+}
+
+impl Display for DocumentId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cui() {
+        let cui = Cui::from_str("C1280500").unwrap();
+        assert!(cui.is_valid());
+        assert_eq!(cui.to_string(), "C1280500");
+        assert_eq!(cui.number(), Some(1280500));
+        for invalid in [
+            "",
+            "C128050",
+            "C12805000",
+            "c1280500",
+            "D1280500",
+            "C12805O0",
+        ] {
+            assert!(Cui::from_str(invalid).is_err(), "{}", invalid);
+        }
+        let lenient = Cui::new_unchecked("C12".to_string());
+        assert!(!lenient.is_valid());
+        assert_eq!(lenient.number(), None);
+        assert!(Cui::from_str("C0000001").unwrap() < cui);
+        assert!(Cui::missing().is_missing());
+        assert!(!Cui::missing().is_valid());
+        assert!(!cui.is_missing());
+    }
+
+    #[test]
+    fn test_document_id() {
+        assert_eq!(DocumentId::from_str("USER").unwrap().as_str(), "USER");
+        assert!(DocumentId::from_str("").is_err());
+        assert!(DocumentId::from_str("  ").is_err());
+    }
+
+    #[test]
+    fn test_ids_serde() {
+        let cui = Cui::from_str("C1280500").unwrap();
+        assert_eq!(serde_json::to_string(&cui).unwrap(), r#""C1280500""#);
+        assert_eq!(serde_json::from_str::<Cui>(r#""C1280500""#).unwrap(), cui);
+        let id = DocumentId::from_str("24119710").unwrap();
+        assert_eq!(serde_json::to_string(&id).unwrap(), r#""24119710""#);
+    }
+}
@@ -0,0 +1,730 @@
+//! Models and a streaming reader for MetaMap's JSON output (`--JSONf`, `--JSONn`).
+//!
+//! The JSON format describes the same output as [XML](crate::xml), with every
+//! document of a run inside a single `AllDocuments` array:
+//!
+//! ```text
+//! {"AllDocuments":[{"Document":{
+//!   "CmdLine":{"Command":"metamap --JSONn","Options":[]},
+//!   "AAs":[],
+//!   "Negations":[],
+//!   "Utterances":[{"PMID":"24119710","UttSection":"ti","UttNum":"1","UttText":"...","Phrases":[...]}]
+//! }}]}
+//! ```
+//!
+//! The types of this module follow that schema field for field. Numbers, which
+//! MetaMap writes as strings, are parsed while deserializing. [`JsonReader`]
+//! yields the documents of a file one at a time, and [`Document::to_outputs`]
+//! projects a document onto fielded MMI records (by way of the
+//! [`machine`](crate::machine) types).
+
+use serde::de::{self, DeserializeOwned, IgnoredAny, Unexpected};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::error;
+use std::fmt::{self, Display};
+use std::io::{self, BufRead, Read};
+
+use crate::{machine, Output, Span};
+
+/// A loosely typed scalar, as MetaMap writes numbers and flags as strings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Scalar {
+    Bool(bool),
+    Int(i64),
+    Text(String),
+}
+
+/// Deserializes a number written either as a string or a number.
+fn number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+    let invalid = |unexpected| de::Error::invalid_value(unexpected, &"a number");
+    match Scalar::deserialize(deserializer)? {
+        Scalar::Int(n) => i32::try_from(n).map_err(|_| invalid(Unexpected::Signed(n))),
+        Scalar::Text(s) => s.trim().parse().map_err(|_| invalid(Unexpected::Str(&s))),
+        Scalar::Bool(b) => Err(invalid(Unexpected::Bool(b))),
+    }
+}
+
+fn optional_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i32>, D::Error> {
+    number(deserializer).map(Some)
+}
+
+/// Deserializes a flag written as `yes`/`no`, `1`/`0` or a boolean.
+fn flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    let invalid = |unexpected| de::Error::invalid_value(unexpected, &"a flag");
+    match Scalar::deserialize(deserializer)? {
+        Scalar::Bool(b) => Ok(b),
+        Scalar::Int(0) => Ok(false),
+        Scalar::Int(1) => Ok(true),
+        Scalar::Int(n) => Err(invalid(Unexpected::Signed(n))),
+        Scalar::Text(s) => match s.trim() {
+            "yes" | "1" | "true" => Ok(true),
+            "no" | "0" | "false" => Ok(false),
+            _ => Err(invalid(Unexpected::Str(&s))),
+        },
+    }
+}
+
+/// Everything MetaMap produced for one document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Document {
+    #[serde(rename = "CmdLine", default)]
+    pub command_line: Option<CommandLine>,
+    #[serde(rename = "AAs", default)]
+    pub abbreviations: Vec<Abbreviation>,
+    #[serde(rename = "Utterances")]
+    pub utterances: Vec<Utterance>,
+}
+
+/// How MetaMap was run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandLine {
+    #[serde(rename = "Command")]
+    pub command: String,
+}
+
+/// An acronym or abbreviation along with its expansion.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Abbreviation {
+    #[serde(rename = "AAText")]
+    pub short_form: String,
+    #[serde(rename = "AAExp")]
+    pub long_form: String,
+    #[serde(rename = "AATokenNum", deserialize_with = "number")]
+    pub short_token_count: i32,
+    #[serde(rename = "AALen", deserialize_with = "number")]
+    pub short_length: i32,
+    #[serde(rename = "AAExpTokenNum", deserialize_with = "number")]
+    pub long_token_count: i32,
+    #[serde(rename = "AAExpLen", deserialize_with = "number")]
+    pub long_length: i32,
+    #[serde(rename = "AAStartPos", deserialize_with = "number")]
+    pub start: i32,
+}
+
+/// A sentence (or title) of a document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Utterance {
+    #[serde(rename = "PMID")]
+    pub pmid: String,
+    /// `ti`, `ab` or `tx`
+    #[serde(rename = "UttSection")]
+    pub section: String,
+    #[serde(rename = "UttNum", deserialize_with = "number")]
+    pub number: i32,
+    #[serde(rename = "UttText")]
+    pub text: String,
+    #[serde(rename = "UttStartPos", deserialize_with = "number")]
+    pub start: i32,
+    #[serde(rename = "UttLength", deserialize_with = "number")]
+    pub length: i32,
+    #[serde(rename = "Phrases", default)]
+    pub phrases: Vec<Phrase>,
+}
+
+/// A phrase of an utterance along with what MetaMap found for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Phrase {
+    #[serde(rename = "PhraseText")]
+    pub text: String,
+    #[serde(rename = "SyntaxUnits", default)]
+    pub syntax_units: Vec<SyntaxUnit>,
+    #[serde(rename = "PhraseStartPos", deserialize_with = "number")]
+    pub start: i32,
+    #[serde(rename = "PhraseLength", deserialize_with = "number")]
+    pub length: i32,
+    #[serde(rename = "Candidates", default)]
+    pub candidates: Vec<Candidate>,
+    #[serde(rename = "Mappings", default)]
+    pub mappings: Vec<Mapping>,
+}
+
+/// One unit of a phrase's syntactic analysis.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyntaxUnit {
+    /// `head`, `mod`, `verb`, `prep`, `punc`, ...
+    #[serde(rename = "SyntaxType")]
+    pub syntax_type: String,
+    #[serde(rename = "LexMatch", default)]
+    pub lex_match: Option<String>,
+    /// the words of the input text the unit covers
+    #[serde(rename = "InputMatch")]
+    pub input_match: String,
+    /// part of speech, if MetaMap tagged the unit
+    #[serde(rename = "LexCat", default)]
+    pub lex_cat: Option<String>,
+    #[serde(rename = "Tokens", default)]
+    pub tokens: Vec<String>,
+}
+
+/// A concept MetaMap considered for a phrase.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candidate {
+    /// negated score, -1000 being a perfect match
+    #[serde(rename = "CandidateScore", deserialize_with = "number")]
+    pub score: i32,
+    #[serde(rename = "CandidateCUI")]
+    pub cui: String,
+    /// the concept string that matched
+    #[serde(rename = "CandidateMatched")]
+    pub matched: String,
+    #[serde(rename = "CandidatePreferred")]
+    pub preferred_name: String,
+    #[serde(rename = "MatchedWords", default)]
+    pub matched_words: Vec<String>,
+    #[serde(rename = "SemTypes", default)]
+    pub semantic_types: Vec<String>,
+    #[serde(rename = "IsHead", deserialize_with = "flag")]
+    pub head: bool,
+    #[serde(rename = "IsOverMatch", deserialize_with = "flag")]
+    pub overmatch: bool,
+    #[serde(rename = "Sources", default)]
+    pub sources: Vec<String>,
+    #[serde(rename = "ConceptPIs", default)]
+    pub positions: Vec<ConceptPosition>,
+    #[serde(rename = "Status", default, deserialize_with = "optional_number")]
+    pub status: Option<i32>,
+    #[serde(rename = "Negated", default, deserialize_with = "flag")]
+    pub negated: bool,
+}
+
+/// Where the text matched by a candidate is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConceptPosition {
+    #[serde(rename = "StartPos", deserialize_with = "number")]
+    pub start: i32,
+    #[serde(rename = "Length", deserialize_with = "number")]
+    pub length: i32,
+}
+
+/// One of the best combinations of candidates covering a phrase.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mapping {
+    /// negated score, -1000 being a perfect mapping
+    #[serde(rename = "MappingScore", deserialize_with = "number")]
+    pub score: i32,
+    #[serde(rename = "MappingCandidates", default)]
+    pub candidates: Vec<Candidate>,
+}
+
+impl Document {
+    /// Id of the document, taken from its first utterance.
+    pub fn document_id(&self) -> Option<&str> {
+        self.utterances.first().map(|u| u.pmid.as_str())
+    }
+
+    /// Projects the document onto fielded MMI records, see [`machine::Mmo::to_outputs`].
+    pub fn to_outputs(&self) -> Vec<Output> {
+        machine::Mmo::from(self.clone()).to_outputs()
+    }
+}
+
+impl From<Document> for machine::Mmo {
+    fn from(document: Document) -> Self {
+        machine::Mmo {
+            command_line: document.command_line.map(|c| c.command),
+            abbreviations: document
+                .abbreviations
+                .into_iter()
+                .map(|aa| machine::Abbreviation {
+                    short_form: aa.short_form,
+                    long_form: aa.long_form,
+                    short_token_count: aa.short_token_count,
+                    short_length: aa.short_length,
+                    long_token_count: aa.long_token_count,
+                    long_length: aa.long_length,
+                    start: aa.start,
+                })
+                .collect(),
+            utterances: document.utterances.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<Utterance> for machine::Utterance {
+    fn from(utterance: Utterance) -> Self {
+        machine::Utterance {
+            id: format!(
+                "{}.{}.{}",
+                utterance.pmid, utterance.section, utterance.number
+            ),
+            text: utterance.text,
+            start: utterance.start,
+            length: utterance.length,
+            phrases: utterance.phrases.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<Phrase> for machine::Phrase {
+    fn from(phrase: Phrase) -> Self {
+        machine::Phrase {
+            text: phrase.text,
+            syntax: phrase
+                .syntax_units
+                .into_iter()
+                .map(|unit| machine::SyntaxUnit {
+                    kind: unit.syntax_type,
+                    input_match: unit
+                        .input_match
+                        .split_whitespace()
+                        .map(str::to_string)
+                        .collect(),
+                    tag: unit.lex_cat,
+                })
+                .collect(),
+            start: phrase.start,
+            length: phrase.length,
+            candidates: phrase.candidates.into_iter().map(Into::into).collect(),
+            mappings: phrase
+                .mappings
+                .into_iter()
+                .map(|mapping| machine::Mapping {
+                    score: mapping.score,
+                    candidates: mapping.candidates.into_iter().map(Into::into).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<Candidate> for machine::Candidate {
+    fn from(candidate: Candidate) -> Self {
+        machine::Candidate {
+            score: candidate.score,
+            cui: candidate.cui,
+            matched: candidate.matched,
+            preferred_name: candidate.preferred_name,
+            matched_words: candidate.matched_words,
+            semantic_types: candidate.semantic_types,
+            head: candidate.head,
+            overmatch: candidate.overmatch,
+            sources: candidate.sources,
+            positions: candidate
+                .positions
+                .into_iter()
+                .map(|p| Span::new(p.start, p.length))
+                .collect(),
+            status: candidate.status,
+            negated: candidate.negated,
+        }
+    }
+}
+
+/// Errors produced while reading JSON output with a [`JsonReader`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum JsonError {
+    /// The underlying reader failed. No further items are produced after this.
+    Io(io::Error),
+    /// The input is not valid JSON, or not laid out as MetaMap writes it.
+    /// No further items are produced after this.
+    Syntax { position: u64, message: String },
+    /// A document was valid JSON but did not match the schema.
+    /// Reading continues with the next document.
+    Structure { position: u64, message: String },
+}
+
+impl JsonError {
+    /// Byte offset in the input the error occurred at (the start of the
+    /// document for structure errors), if any.
+    pub fn position(&self) -> Option<u64> {
+        match self {
+            JsonError::Io(_) => None,
+            JsonError::Syntax { position, .. } | JsonError::Structure { position, .. } => {
+                Some(*position)
+            }
+        }
+    }
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonError::Io(e) => write!(f, "could not read input: {}", e),
+            JsonError::Syntax { position, message } => {
+                write!(f, "invalid JSON at byte {}: {}", position, message)
+            }
+            JsonError::Structure { position, message } => {
+                write!(f, "document at byte {}: {}", position, message)
+            }
+        }
+    }
+}
+
+impl error::Error for JsonError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            JsonError::Io(e) => Some(e),
+            JsonError::Syntax { .. } | JsonError::Structure { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for JsonError {
+    fn from(e: io::Error) -> Self {
+        JsonError::Io(e)
+    }
+}
+
+/// Message of a serde_json error, without the line and column, which would be
+/// relative to the value being read rather than to the input.
+fn message(error: &serde_json::Error) -> String {
+    let text = error.to_string();
+    let location = format!(" at line {} column {}", error.line(), error.column());
+    text.strip_suffix(&location).unwrap_or(&text).to_string()
+}
+
+/// Whether `text` (the start of an input) looks like MetaMap JSON output.
+pub fn looks_like_json_output(text: &str) -> bool {
+    text.trim_start()
+        .strip_prefix('{')
+        .is_some_and(|rest| rest.trim_start().starts_with("\"AllDocuments\""))
+}
+
+/// A [`BufRead`] keeping track of how many bytes were read from it.
+struct Counted<R> {
+    inner: R,
+    position: u64,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Counted<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.position += amt as u64;
+        self.inner.consume(amt)
+    }
+}
+
+/// The part of the input the reader is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// between top-level objects
+    TopLevel,
+    /// among the keys of a top-level object
+    Keys,
+    /// inside the `AllDocuments` array
+    Documents,
+    Finished,
+}
+
+/// An element of the `AllDocuments` array.
+#[derive(Deserialize)]
+struct Entry {
+    #[serde(rename = "Document")]
+    document: Document,
+}
+
+/// Iterator over the [`Document`]s of JSON output read from any [`BufRead`].
+///
+/// The `AllDocuments` array is read one element at a time, so only one document
+/// is held in memory however large the file is. Inputs with several top-level
+/// objects (e.g. from concatenated runs) are read one after the other.
+///
+/// ```rust
+/// use mmi_parser::json::JsonReader;
+///
+/// let input = r#"{"AllDocuments":[{"Document":{"AAs":[],"Utterances":[]}}]}"#;
+/// let documents = JsonReader::new(input.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(documents.len(), 1);
+/// ```
+pub struct JsonReader<R> {
+    reader: Counted<R>,
+    state: State,
+}
+
+impl<R: BufRead> JsonReader<R> {
+    /// Creates a new reader over `reader`.
+    pub fn new(reader: R) -> Self {
+        JsonReader {
+            reader: Counted {
+                inner: reader,
+                position: 0,
+            },
+            state: State::TopLevel,
+        }
+    }
+
+    /// Skips whitespace and returns the next byte without consuming it.
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            let whitespace = buffer
+                .iter()
+                .take_while(|b| b.is_ascii_whitespace())
+                .count();
+            if whitespace == 0 {
+                return Ok(buffer.first().copied());
+            }
+            self.reader.consume(whitespace);
+        }
+    }
+
+    fn unexpected(&self, found: Option<u8>, expected: &str) -> JsonError {
+        let found = match found {
+            Some(b) => format!("`{}`", char::from(b)),
+            None => "the end of the input".to_string(),
+        };
+        JsonError::Syntax {
+            position: self.reader.position,
+            message: format!("expected {} but found {}", expected, found),
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), JsonError> {
+        match self.peek()? {
+            Some(b) if b == byte => {
+                self.reader.consume(1);
+                Ok(())
+            }
+            other => Err(self.unexpected(other, &format!("`{}`", char::from(byte)))),
+        }
+    }
+
+    /// Consumes the bytes of the number at the start of the input.
+    fn take_number(&mut self) -> io::Result<Vec<u8>> {
+        let mut number = Vec::new();
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            let length = buffer
+                .iter()
+                .take_while(|b| matches!(b, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E'))
+                .count();
+            number.extend_from_slice(&buffer[..length]);
+            let more = length > 0 && length == buffer.len();
+            self.reader.consume(length);
+            if !more {
+                return Ok(number);
+            }
+        }
+    }
+
+    /// Reads a single JSON value, leaving the input right after it.
+    fn value<T: DeserializeOwned>(&mut self) -> Result<T, JsonError> {
+        let result = match self.peek()? {
+            // serde_json reads one byte past a number to find where it ends, and
+            // that byte would be dropped along with the deserializer
+            Some(b'-' | b'0'..=b'9') => serde_json::from_slice(&self.take_number()?),
+            _ => {
+                let mut deserializer = serde_json::Deserializer::from_reader(&mut self.reader);
+                T::deserialize(&mut deserializer)
+            }
+        };
+        result.map_err(|e| {
+            if e.is_io() {
+                JsonError::Io(e.into())
+            } else {
+                JsonError::Syntax {
+                    position: self.reader.position,
+                    message: message(&e),
+                }
+            }
+        })
+    }
+
+    /// Moves through the input up to the next document, returning it as
+    /// generic json along with its position, or `None` at the end of the input.
+    fn next_document(&mut self) -> Result<Option<(u64, serde_json::Value)>, JsonError> {
+        loop {
+            match (self.state, self.peek()?) {
+                (State::Finished, _) | (State::TopLevel, None) => return Ok(None),
+                (State::TopLevel, Some(b'{')) => {
+                    self.reader.consume(1);
+                    self.state = State::Keys;
+                }
+                (State::TopLevel, other) => return Err(self.unexpected(other, "`{`")),
+                (State::Keys, Some(b'}')) => {
+                    self.reader.consume(1);
+                    self.state = State::TopLevel;
+                }
+                (State::Keys | State::Documents, Some(b',')) => self.reader.consume(1),
+                (State::Keys, Some(b'"')) => {
+                    let key: String = self.value()?;
+                    self.expect(b':')?;
+                    if key == "AllDocuments" {
+                        self.expect(b'[')?;
+                        self.state = State::Documents;
+                    } else {
+                        self.value::<IgnoredAny>()?;
+                    }
+                }
+                (State::Keys, other) => return Err(self.unexpected(other, "a key or `}`")),
+                (State::Documents, Some(b']')) => {
+                    self.reader.consume(1);
+                    self.state = State::Keys;
+                }
+                (State::Documents, Some(b'{')) => {
+                    let position = self.reader.position;
+                    return Ok(Some((position, self.value()?)));
+                }
+                (State::Documents, other) => {
+                    return Err(self.unexpected(other, "a document or `]`"))
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for JsonReader<R> {
+    type Item = Result<Document, JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_document() {
+            Ok(Some((position, value))) => Some(
+                serde_json::from_value::<Entry>(value)
+                    .map(|entry| entry.document)
+                    .map_err(|e| JsonError::Structure {
+                        position,
+                        message: message(&e),
+                    }),
+            ),
+            Ok(None) => {
+                self.state = State::Finished;
+                None
+            }
+            Err(e) => {
+                self.state = State::Finished;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"{"AllDocuments":[
+{"Document":{
+  "CmdLine":{"Command":"metamap --JSONf 2","Options":[]},
+  "AAs":[{"AAText":"MI","AAExp":"heart attack","AATokenNum":"1","AALen":"2","AAExpTokenNum":"3","AAExpLen":"12","AAStartPos":"38","AACUIs":[]}],
+  "Negations":[],
+  "Utterances":[{
+    "PMID":"24119710","UttSection":"ti","UttNum":"1","UttText":"Effects of heart attack \"MI\"","UttStartPos":"0","UttLength":"28",
+    "Phrases":[{
+      "PhraseText":"of heart attack",
+      "SyntaxUnits":[
+        {"SyntaxType":"prep","LexMatch":"of","InputMatch":"of","LexCat":"prep","Tokens":["of"]},
+        {"SyntaxType":"head","LexMatch":"heart attack","InputMatch":"heart attack","LexCat":"noun","Tokens":["heart","attack"]}
+      ],
+      "PhraseStartPos":"8","PhraseLength":"15",
+      "Candidates":[],
+      "Mappings":[{"MappingScore":"-1000","MappingCandidates":[{
+        "CandidateScore":"-1000","CandidateCUI":"C0027051","CandidateMatched":"Heart attack",
+        "CandidatePreferred":"Myocardial Infarction","MatchedWords":["heart","attack"],"SemTypes":["dsyn"],
+        "MatchMaps":[],"IsHead":"yes","IsOverMatch":"no","Sources":["MSH","MTH"],
+        "ConceptPIs":[{"StartPos":"11","Length":"12"}],"Status":"0","Negated":"1"
+      }]}]
+    }]
+  }]
+}},
+{"Document":{"CmdLine":{"Command":"metamap --JSONf 2","Options":[]},"AAs":[],"Negations":[],"Utterances":[]}}
+]}
+"#;
+
+    #[test]
+    fn test_json_reader() {
+        let documents = JsonReader::new(SAMPLE.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(documents.len(), 2);
+        let document = &documents[0];
+        assert_eq!(
+            document.command_line.as_ref().unwrap().command,
+            "metamap --JSONf 2"
+        );
+        assert_eq!(document.document_id(), Some("24119710"));
+        assert_eq!(document.abbreviations[0].start, 38);
+        let utterance = &document.utterances[0];
+        assert_eq!(utterance.number, 1);
+        assert_eq!(utterance.text, "Effects of heart attack \"MI\"");
+        let phrase = &utterance.phrases[0];
+        assert_eq!(phrase.syntax_units[1].tokens, ["heart", "attack"]);
+        let candidate = &phrase.mappings[0].candidates[0];
+        assert_eq!(candidate.score, -1000);
+        assert_eq!(candidate.status, Some(0));
+        assert!(candidate.head && !candidate.overmatch && candidate.negated);
+        assert_eq!(candidate.positions[0].length, 12);
+        assert!(documents[1].utterances.is_empty());
+    }
+
+    #[test]
+    fn test_json_to_outputs() {
+        let document = JsonReader::new(SAMPLE.as_bytes()).next().unwrap().unwrap();
+        let outputs = document.to_outputs();
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(
+            outputs[0].to_string(),
+            "24119710|MMI|1000.00|Myocardial Infarction|C0027051|[dsyn]|[\"Heart attack\"-ti-1-\"heart attack\"-noun-1]|TI|11/12|"
+        );
+        assert_eq!(
+            outputs[1].to_string(),
+            "24119710|AA|MI|heart attack|1|2|3|12|38:2"
+        );
+    }
+
+    #[test]
+    fn test_json_reader_errors() {
+        let input = r#"{"AllDocuments":[{"Document":{"Utterances":[{"PMID":"1"}]}}, {"Document":{"Utterances":[]}}, {"Document": ]}"#;
+        let items = JsonReader::new(input.as_bytes()).collect::<Vec<_>>();
+        assert_eq!(items.len(), 3);
+        assert!(matches!(
+            &items[0],
+            Err(JsonError::Structure { position: 17, message }) if message.contains("UttSection")
+        ));
+        assert!(items[1].is_ok());
+        assert!(matches!(items[2], Err(JsonError::Syntax { .. })));
+        let items = JsonReader::new(&b"[]"[..]).collect::<Vec<_>>();
+        assert!(matches!(
+            items[..],
+            [Err(JsonError::Syntax { position: 0, .. })]
+        ));
+        assert!(looks_like_json_output("{\n  \"AllDocuments\": ["));
+        assert!(!looks_like_json_output("{\"MMI\": {}}"));
+    }
+
+    #[test]
+    fn test_json_reader_numeric_keys() {
+        let document = r#"{"Document":{"AAs":[],"Utterances":[]}}"#;
+        for input in [
+            format!(r#"{{"AllDocuments":[{}],"Version":2}}"#, document),
+            format!(r#"{{"Version":-2.5e1 , "AllDocuments":[{}]}}"#, document),
+            format!(
+                r#"{{"Version":2}}{{"AllDocuments":[{}],"Count":10}}"#,
+                document
+            ),
+        ] {
+            for capacity in [1, 3, 8192] {
+                let reader = io::BufReader::with_capacity(capacity, input.as_bytes());
+                let items = JsonReader::new(reader).collect::<Vec<_>>();
+                assert_eq!(items.len(), 1, "{}", input);
+                assert!(items[0].is_ok(), "{}: {:?}", input, items[0]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_numbers_and_flags() {
+        let candidate: Candidate = serde_json::from_str(
+            r#"{"CandidateScore":-861,"CandidateCUI":"C1","CandidateMatched":"a","CandidatePreferred":"A","IsHead":true,"IsOverMatch":0}"#,
+        )
+        .unwrap();
+        assert_eq!(candidate.score, -861);
+        assert!(candidate.head && !candidate.overmatch && !candidate.negated);
+        assert_eq!(candidate.status, None);
+        assert!(
+            serde_json::from_str::<ConceptPosition>(r#"{"StartPos":"x","Length":"1"}"#).is_err()
+        );
+    }
+}
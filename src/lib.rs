@@ -7,20 +7,62 @@
 //! and relies on MetaMap 2016 or newer.
 //!
 //! The main functionality is encompassed in [`MmiOutput`], [`AaOutput`], and [`parse_record`].
+//! To parse whole files or streams, [`MmiReader`] applies [`parse_record`] to every line
+//! of any [`BufRead`](std::io::BufRead) and reports line numbers alongside each record,
+//! and [`DocumentReader`] groups those records into one [`Document`] per document id.
+//!
+//! MetaMap's Prolog machine output (`--machine_output`) is handled by the [`machine`] module,
+//! which can also project it onto [`MmiOutput`] records. MetaMap's XML output (`--XMLf`,
+//! `--XMLn`) is read into the same structures by the [`xml`] module, and JSON output
+//! (`--JSONf`, `--JSONn`) is modelled by the [`json`] module. The fielded output of
+//! MetaMap Lite is read by the [`lite`] module, and MetaMap's default human-readable
+//! output by the [`human`] module. The [`rows`] module flattens records into table rows
+//! for CSV and similar formats, and with the `parquet` feature the `columnar` module
+//! builds Apache Arrow record batches from them. With the `sqlite` feature, the `sqlite`
+//! module writes them into a normalized SQLite database.
 //!
 //! For questions on implementations of the parsing algorithms for specific sections,
 //! please consult the [source](https://github.com/UK-IPOP) which contains well-labeled
 //! and fairly documented functions to parse each type.
+//!
+//! The library never writes to stdout or stderr itself. Diagnostic messages are
+//! emitted through the [`log`](https://docs.rs/log) facade, so embedding
+//! applications decide whether and where they go by installing a logger.
 
 extern crate core;
 
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::{self, Display};
-use std::num::ParseIntError;
 use std::str::FromStr;
 use std::{error, result};
 
+mod aggregate;
+mod borrowed;
+#[cfg(feature = "parquet")]
+pub mod columnar;
+mod document;
+pub mod human;
+mod ids;
+pub mod json;
+pub mod lite;
+pub mod machine;
+mod reader;
+pub mod rows;
+mod semantic_types;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+mod tree_code;
+pub mod xml;
+
+pub use borrowed::{parse_record_ref, AaOutputRef, MmiOutputRef, OutputRef, TriggerRef};
+pub use document::{Document, DocumentReader};
+pub use ids::{Cui, DocumentId};
+pub use reader::{MmiReader, ParsedLine, ReadError};
+pub use semantic_types::{SemanticGroup, SemanticType, SEMANTIC_TYPES_VERSION};
+pub use tree_code::TreeCode;
+
 /// ValueError occurs when an invalid value was provided
 #[derive(Debug)]
 pub struct ValueError;
@@ -33,8 +75,256 @@ impl Display for ValueError {
 
 impl error::Error for ValueError {}
 
-/// A custom result type implementing [`ValueError`]
-type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
+/// Errors produced while parsing a fielded MMI/AA record.
+///
+/// Every variant that points at a specific part of the record carries the
+/// name of the field it came from, the offending substring, and the
+/// zero-based byte column at which that substring starts within the line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseError {
+    /// The line did not split into the expected number of pipe-separated fields.
+    FieldCount { expected: usize, found: usize },
+    /// A labeled record passed to an `assemble` function lacks a required field.
+    MissingField { field: &'static str },
+    /// A value expected to be an integer could not be parsed as one.
+    InvalidInt {
+        field: &'static str,
+        value: String,
+        column: usize,
+    },
+    /// A value expected to be a float could not be parsed as one.
+    InvalidFloat {
+        field: &'static str,
+        value: String,
+        column: usize,
+    },
+    /// A location could not be parsed into a [`Location`] (it had an empty section tag).
+    UnknownLocation {
+        field: &'static str,
+        value: String,
+        column: usize,
+    },
+    /// A trigger did not split into the expected sextuple.
+    MalformedTrigger {
+        value: String,
+        column: usize,
+        parts: usize,
+    },
+    /// Any other value that does not fit the format of its field.
+    InvalidValue {
+        field: &'static str,
+        value: String,
+        column: usize,
+    },
+}
+
+impl ParseError {
+    /// Name of the field the error originated from, if it points at one.
+    pub fn field(&self) -> Option<&'static str> {
+        match self {
+            ParseError::FieldCount { .. } => None,
+            ParseError::MalformedTrigger { .. } => Some("triggers"),
+            ParseError::MissingField { field } => Some(field),
+            ParseError::InvalidInt { field, .. }
+            | ParseError::InvalidFloat { field, .. }
+            | ParseError::UnknownLocation { field, .. }
+            | ParseError::InvalidValue { field, .. } => Some(field),
+        }
+    }
+
+    /// The offending substring, if the error points at one.
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            ParseError::FieldCount { .. } | ParseError::MissingField { .. } => None,
+            ParseError::InvalidInt { value, .. }
+            | ParseError::InvalidFloat { value, .. }
+            | ParseError::UnknownLocation { value, .. }
+            | ParseError::MalformedTrigger { value, .. }
+            | ParseError::InvalidValue { value, .. } => Some(value),
+        }
+    }
+
+    /// Zero-based byte column of the offending substring within the line.
+    pub fn column(&self) -> Option<usize> {
+        match self {
+            ParseError::FieldCount { .. } | ParseError::MissingField { .. } => None,
+            ParseError::InvalidInt { column, .. }
+            | ParseError::InvalidFloat { column, .. }
+            | ParseError::UnknownLocation { column, .. }
+            | ParseError::MalformedTrigger { column, .. }
+            | ParseError::InvalidValue { column, .. } => Some(*column),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::FieldCount { expected, found } => write!(
+                f,
+                "expected {} pipe-separated fields, found {}",
+                expected, found
+            ),
+            ParseError::MissingField { field } => write!(f, "missing field `{}`", field),
+            ParseError::InvalidInt {
+                field,
+                value,
+                column,
+            } => write!(
+                f,
+                "invalid integer {:?} in field `{}` at column {}",
+                value, field, column
+            ),
+            ParseError::InvalidFloat {
+                field,
+                value,
+                column,
+            } => write!(
+                f,
+                "invalid float {:?} in field `{}` at column {}",
+                value, field, column
+            ),
+            ParseError::UnknownLocation {
+                field,
+                value,
+                column,
+            } => write!(
+                f,
+                "unknown location {:?} in field `{}` at column {}",
+                value, field, column
+            ),
+            ParseError::MalformedTrigger {
+                value,
+                column,
+                parts,
+            } => write!(
+                f,
+                "malformed trigger {:?} at column {}, expected sextuple (6) but found {} parts",
+                value, column, parts
+            ),
+            ParseError::InvalidValue {
+                field,
+                value,
+                column,
+            } => write!(
+                f,
+                "unexpected value {:?} in field `{}` at column {}",
+                value, field, column
+            ),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// A custom result type implementing [`ParseError`]
+type Result<T> = std::result::Result<T, ParseError>;
+
+/// How strictly the record identifiers ([`DocumentId`] and [`Cui`]) are validated.
+///
+/// Validation is opt-in: [`parse_record`] and the other functions without a
+/// choice of strictness parse leniently, as before these types existed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Malformed identifiers fail the record.
+    Strict,
+    /// Malformed identifiers are kept as they are and reported as warnings.
+    #[default]
+    Lenient,
+}
+
+/// Which program wrote the fielded output being parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    /// MetaMap, parsed with [`parse_record`].
+    #[default]
+    MetaMap,
+    /// MetaMap Lite, parsed with [`lite::parse_record`].
+    Lite,
+}
+
+/// Collects the problems [`Strictness::Lenient`] tolerates while a record is parsed.
+pub(crate) struct Warnings {
+    strictness: Strictness,
+    found: Vec<ParseError>,
+}
+
+impl Warnings {
+    pub(crate) fn new(strictness: Strictness) -> Self {
+        Warnings {
+            strictness,
+            found: Vec::new(),
+        }
+    }
+
+    /// Fails with `error` when strict, otherwise records it and carries on.
+    pub(crate) fn check(&mut self, valid: bool, error: impl FnOnce() -> ParseError) -> Result<()> {
+        match (valid, self.strictness) {
+            (true, _) => Ok(()),
+            (false, Strictness::Strict) => Err(error()),
+            (false, Strictness::Lenient) => {
+                let error = error();
+                log::debug!("Keeping invalid value: {}", error);
+                self.found.push(error);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Field names of an MMI record in the order they appear on the line.
+const MMI_FIELDS: [&str; 10] = [
+    "id",
+    "mmi",
+    "score",
+    "name",
+    "cui",
+    "semantic_types",
+    "triggers",
+    "location",
+    "positional_info",
+    "tree_codes",
+];
+
+/// Field names of an AA record in the order they appear on the line.
+const AA_FIELDS: [&str; 9] = [
+    "id",
+    "abbreviation_type",
+    "short_form",
+    "long_form",
+    "short_token_count",
+    "short_character_count",
+    "long_token_count",
+    "long_character_count",
+    "positional_info",
+];
+
+/// Byte offset of `inner` within `outer`.
+/// `inner` must be a subslice of `outer`.
+fn offset_in(outer: &str, inner: &str) -> usize {
+    inner.as_ptr() as usize - outer.as_ptr() as usize
+}
+
+/// Looks up a labeled field, erroring instead of panicking if it is absent.
+fn lookup_field<'a>(parts: &HashMap<&str, &'a str>, name: &'static str) -> Result<&'a str> {
+    parts
+        .get(name)
+        .copied()
+        .ok_or(ParseError::MissingField { field: name })
+}
+
+/// Looks up every field in `names` from a labeled record, in line order.
+fn ordered_fields<'a, const N: usize>(
+    parts: &HashMap<&str, &'a str>,
+    names: [&'static str; N],
+) -> Result<[&'a str; N]> {
+    let mut fields = [""; N];
+    for (field, name) in fields.iter_mut().zip(names) {
+        *field = lookup_field(parts, name)?;
+    }
+    Ok(fields)
+}
 
 /// Splits the provided string reference on vertical bar (pipe symbol)
 /// and collects split into vector.
@@ -44,54 +334,180 @@ fn split_text(text: &str) -> Vec<&str> {
 
 /// Labels the parts of the pipe-split string using MMI field labels.
 /// Returns a hashmap of field names as keys and their values from the vector.
-fn label_mmi_parts(parts: Vec<&str>) -> Result<HashMap<&str, &str>> {
-    if parts.len() != 10 {
-        println!(
+///
+/// Records are parsed without labeling them (see [`parse_record_ref`]), so
+/// only the tests of [`MmiOutput::assemble`] still need this.
+#[cfg(test)]
+pub(crate) fn label_mmi_parts(parts: Vec<&str>) -> Result<HashMap<&str, &str>> {
+    if parts.len() != MMI_FIELDS.len() {
+        log::debug!(
             "Record is not of the right length, expected 10 pipe-separated components, found {}",
             parts.len()
         );
-        return Err(Box::new(ValueError));
-    }
-    let mut map = HashMap::new();
-    map.insert("id", parts[0]);
-    map.insert("mmi", parts[1]);
-    map.insert("score", parts[2]);
-    map.insert("name", parts[3]);
-    map.insert("cui", parts[4]);
-    map.insert("semantic_types", parts[5]);
-    map.insert("triggers", parts[6]);
-    map.insert("location", parts[7]);
-    map.insert("positional_info", parts[8]);
-    map.insert("tree_codes", parts[9]);
-    Ok(map)
+        return Err(ParseError::FieldCount {
+            expected: MMI_FIELDS.len(),
+            found: parts.len(),
+        });
+    }
+    Ok(MMI_FIELDS.into_iter().zip(parts).collect())
 }
 
 /// Parses out semantic type field by removing brackets and splitting on commas.
-fn parse_semantic_types(semantic_types: &str) -> Vec<String> {
+fn parse_semantic_types(semantic_types: &str) -> Vec<&str> {
     let cleaned = semantic_types.trim_start_matches('[').trim_end_matches(']');
-    let clean_list = cleaned.split(',').map(|x| x.to_string()).collect();
-    clean_list
+    cleaned.split(',').collect()
 }
 
-/// Enumeration for Location options.
-#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
-pub enum Location {
+/// A single section tag such as `TI` (title) or `AB` (abstract).
+///
+/// Tags other than the ones MetaMap has always emitted are kept as
+/// [`Section::Other`] (uppercased) instead of failing the record.
+#[derive(PartialEq, Eq, Debug, Clone, Hash)]
+pub enum Section {
     TI,
     AB,
     TX,
-    Tiab,
+    Other(String),
+}
+
+impl Section {
+    /// The tag as it appears in field 8 of an MMI record.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Section::TI => "TI",
+            Section::AB => "AB",
+            Section::TX => "TX",
+            Section::Other(tag) => tag,
+        }
+    }
+}
+
+impl FromStr for Section {
+    type Err = ValueError;
+    /// Parses a section tag, case-insensitively. Only empty tags are invalid.
+    fn from_str(s: &str) -> std::result::Result<Section, ValueError> {
+        let tag = s.trim().to_uppercase();
+        match tag.as_str() {
+            "" => Err(ValueError),
+            "TI" => Ok(Section::TI),
+            "AB" => Ok(Section::AB),
+            "TX" => Ok(Section::TX),
+            _ => Ok(Section::Other(tag)),
+        }
+    }
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Serialize for Section {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Section {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> result::Result<Self, D::Error> {
+        let tag = String::deserialize(deserializer)?;
+        Section::from_str(&tag).map_err(|_| serde::de::Error::custom("empty section tag"))
+    }
+}
+
+/// Where a concept was found: an ordered set of [`Section`] tags.
+///
+/// MetaMap writes these `;`-separated (e.g. `TI;AB`). Order of first
+/// appearance is preserved and repeated tags are dropped.
+///
+/// Serializes as a list of tags (`["TI", "AB"]`). Older releases serialized
+/// the four combinations they knew as `"TI"`, `"AB"`, `"TX"` and `"Tiab"`;
+/// [`Location::legacy_name`] gives that form and deserialization accepts both.
+#[derive(PartialEq, Eq, Debug, Clone, Hash, Serialize)]
+pub struct Location(Vec<Section>);
+
+impl Location {
+    /// Creates a location from its sections, dropping repeats.
+    pub fn new<I: IntoIterator<Item = Section>>(sections: I) -> Location {
+        let mut unique: Vec<Section> = Vec::new();
+        for section in sections {
+            if !unique.contains(&section) {
+                unique.push(section);
+            }
+        }
+        Location(unique)
+    }
+
+    /// The sections in the order they were written.
+    pub fn sections(&self) -> &[Section] {
+        &self.0
+    }
+
+    /// Whether the location includes `section`.
+    pub fn contains(&self, section: &Section) -> bool {
+        self.0.contains(section)
+    }
+
+    /// The name older releases used for this location in json, if it had one.
+    ///
+    /// Only `TI`, `AB`, `TX` and `TI;AB` (as `"Tiab"`) have legacy names.
+    pub fn legacy_name(&self) -> Option<&'static str> {
+        match self.0.as_slice() {
+            [Section::TI] => Some("TI"),
+            [Section::AB] => Some("AB"),
+            [Section::TX] => Some("TX"),
+            [Section::TI, Section::AB] => Some("Tiab"),
+            _ => None,
+        }
+    }
+}
+
+impl From<Section> for Location {
+    fn from(section: Section) -> Self {
+        Location(vec![section])
+    }
 }
 
 impl FromStr for Location {
     type Err = ValueError;
-    /// Parses a Location type from a string reference.
+    /// Parses a `;`-separated list of section tags. Errors on empty tags.
     fn from_str(s: &str) -> std::result::Result<Location, ValueError> {
-        match s.to_uppercase().as_str() {
-            "TI" => Ok(Location::TI),
-            "AB" => Ok(Location::AB),
-            "TX" => Ok(Location::TX),
-            "TI;AB" => Ok(Location::Tiab),
-            _ => Err(ValueError),
+        let sections = s
+            .split(';')
+            .map(Section::from_str)
+            .collect::<result::Result<Vec<_>, _>>()?;
+        Ok(Location::new(sections))
+    }
+}
+
+impl Display for Location {
+    /// Writes the location the way it appears in field 8 of an MMI record.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, section) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ";")?;
+            }
+            write!(f, "{}", section)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de> for Location {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Sections(Vec<Section>),
+            Legacy(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Sections(sections) if !sections.is_empty() => Ok(Location::new(sections)),
+            Repr::Sections(_) => Err(serde::de::Error::custom("empty location")),
+            Repr::Legacy(name) if name == "Tiab" => Ok(Location::new([Section::TI, Section::AB])),
+            Repr::Legacy(name) => Location::from_str(&name)
+                .map_err(|_| serde::de::Error::custom(format!("invalid location {:?}", name))),
         }
     }
 }
@@ -99,11 +515,11 @@ impl FromStr for Location {
 /// Parses the tree codes by splitting string reference on semicolon and
 /// collecting into vector.
 /// Returns Optional Vector because tree-codes could be None.
-fn parse_tree_codes(codes: &str) -> Option<Vec<String>> {
+fn parse_tree_codes(codes: &str) -> Option<Vec<&str>> {
     if codes.is_empty() {
         return None;
     }
-    Some(codes.split(';').map(|x| x.to_string()).collect())
+    Some(codes.split(';').collect())
 }
 
 /// Utility function for splitting a string reference on a given pattern
@@ -111,25 +527,39 @@ fn parse_tree_codes(codes: &str) -> Option<Vec<String>> {
 ///  
 /// This was necessary due to MMI output containing literal-quoted strings with
 /// split characters ("," or "-") inside them.
-fn split_with_quote_context(x: &str, pattern: char) -> Vec<String> {
+fn split_with_quote_context(x: &str, pattern: char) -> Vec<&str> {
     let mut is_in_quotes = false;
     let mut start_position = 0;
-    let final_position = x.len();
-    let mut parts: Vec<String> = Vec::new();
-    for (i, c) in x.chars().enumerate() {
+    let mut parts: Vec<&str> = Vec::new();
+    for (i, c) in x.char_indices() {
         if c == '\"' {
             is_in_quotes = !is_in_quotes;
         } else if c == pattern && !is_in_quotes {
-            parts.push(x[start_position..i].to_string());
-            start_position = i + 1;
-        } else if i == final_position - 1 {
-            // last part
-            parts.push(x[start_position..final_position].to_string());
+            parts.push(&x[start_position..i]);
+            start_position = i + c.len_utf8();
         }
     }
+    if start_position < x.len() {
+        // last part
+        parts.push(&x[start_position..]);
+    }
     parts
 }
 
+/// Removes MetaMap's literal quotes from a value.
+/// Only allocates when quotes appear somewhere other than around the value.
+fn unquote(x: &str) -> Cow<'_, str> {
+    let inner = x
+        .strip_prefix('\"')
+        .and_then(|x| x.strip_suffix('\"'))
+        .unwrap_or(x);
+    if inner.contains('\"') {
+        Cow::Owned(x.replace('\"', ""))
+    } else {
+        Cow::Borrowed(inner)
+    }
+}
+
 /// Struct to represent Trigger information.
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct Trigger {
@@ -147,23 +577,42 @@ pub struct Trigger {
     pub negation: bool,
 }
 
+impl Display for Trigger {
+    /// Writes the trigger as the quoted sextuple used in MMI records.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "\"{}\"-{}-{}-\"{}\"-{}-{}",
+            self.name,
+            self.loc.to_string().to_lowercase(),
+            self.loc_position,
+            self.text,
+            self.part_of_speech,
+            u8::from(self.negation)
+        )
+    }
+}
+
 /// Utility function to convert string reference to boolean.
 ///
 /// Will error if string reference is not "1" or "0" because
 /// that is the expected output from MetaMap.
-fn parse_bool(x: &str) -> Result<bool> {
+fn parse_bool(x: &str) -> result::Result<bool, ValueError> {
     match x {
         "1" => Ok(true),
         "0" => Ok(false),
         _ => {
-            println!("Unexpected boolean: {}", x);
-            Err(Box::new(ValueError))
+            log::debug!("Unexpected boolean: {}", x);
+            Err(ValueError)
         }
     }
 }
 
 impl Trigger {
     /// New function to initialize a Trigger.
+    ///
+    /// Errors if `loc` is not a valid [`Location`] or `loc_pos` is not an integer.
+    /// Since the values are not part of a record, the reported column is always 0.
     pub fn new(
         n: &str,
         loc: &str,
@@ -171,82 +620,103 @@ impl Trigger {
         t: &str,
         part_of_speech: &str,
         neg: bool,
-    ) -> Trigger {
-        Trigger {
+    ) -> Result<Trigger> {
+        let loc = Location::from_str(loc).map_err(|_| ParseError::UnknownLocation {
+            field: "triggers",
+            value: loc.to_string(),
+            column: 0,
+        })?;
+        let loc_position = loc_pos.parse::<i32>().map_err(|_| ParseError::InvalidInt {
+            field: "triggers",
+            value: loc_pos.to_string(),
+            column: 0,
+        })?;
+        Ok(Trigger {
             name: n.replace('\"', ""),
-            loc: Location::from_str(loc).expect("unable to parse Location"),
-            loc_position: loc_pos
-                .parse::<i32>()
-                .expect("unable to parse integer from location"),
+            loc,
+            loc_position,
             text: t.replace('\"', ""),
             part_of_speech: part_of_speech.replace('\"', ""),
             negation: neg,
-        }
+        })
     }
 }
 
 /// Parses [`Trigger`] instances from string reference.
-fn parse_triggers(info: &str) -> Result<Vec<Trigger>> {
-    let mut triggers: Vec<Trigger> = Vec::new();
+///
+/// `column` is the byte column at which `info` starts within the record
+/// and is used to locate errors.
+fn parse_triggers(info: &str, column: usize) -> Result<Vec<TriggerRef<'_>>> {
+    let mut triggers: Vec<TriggerRef> = Vec::new();
     let trigger_list = split_with_quote_context(info, ',');
     for t in trigger_list {
         let clean = t.trim_start_matches('[').trim_end_matches(']');
         let parts = split_with_quote_context(clean, '-');
         if parts.len() != 6 {
-            println!(
+            log::debug!(
                 "Trigger format does not make sense, expected sextuple (6), got {} parts instead.",
                 &parts.len()
             );
-            return Err(Box::new(ValueError));
-        } else {
-            // valid shape
-            let negation = parse_bool(&parts[5])?;
-            let trigger = Trigger::new(
-                &parts[0], &parts[1], &parts[2], &parts[3], &parts[4], negation,
-            );
-            triggers.push(trigger)
+            return Err(ParseError::MalformedTrigger {
+                value: t.to_string(),
+                column: column + offset_in(info, t),
+                parts: parts.len(),
+            });
         }
+        // valid shape
+        let col = |part: &str| column + offset_in(info, part);
+        let loc = Location::from_str(parts[1]).map_err(|_| ParseError::UnknownLocation {
+            field: "triggers",
+            value: parts[1].to_string(),
+            column: col(parts[1]),
+        })?;
+        let loc_position = parts[2]
+            .parse::<i32>()
+            .map_err(|_| ParseError::InvalidInt {
+                field: "triggers",
+                value: parts[2].to_string(),
+                column: col(parts[2]),
+            })?;
+        let negation = parse_bool(parts[5]).map_err(|_| ParseError::InvalidValue {
+            field: "triggers",
+            value: parts[5].to_string(),
+            column: col(parts[5]),
+        })?;
+        triggers.push(TriggerRef {
+            name: unquote(parts[0]),
+            loc,
+            loc_position,
+            text: unquote(parts[3]),
+            part_of_speech: unquote(parts[4]),
+            negation,
+        })
     }
     Ok(triggers)
 }
 
 /// Splits on commas *not* inside brackets.
 /// Similar to [split_with_quote_context] except applies to brackets instead of quotes.
-fn split_with_bracket_context(x: &str) -> Vec<String> {
+fn split_with_bracket_context(x: &str) -> Vec<&str> {
     let mut is_in_brackets = false;
     let mut start_position = 0;
-    let final_position = x.len();
-    let mut parts: Vec<String> = Vec::new();
-    for (i, c) in x.chars().enumerate() {
-        if c == '[' {
+    let mut parts: Vec<&str> = Vec::new();
+    for (i, c) in x.char_indices() {
+        if c == '[' || c == ']' {
             is_in_brackets = !is_in_brackets;
-        } else if c == ']' {
-            is_in_brackets = !is_in_brackets;
-            if i == final_position - 1 {
-                // last part
-                parts.push(x[start_position..final_position].to_string());
-            }
         } else if c == ',' && !is_in_brackets {
-            parts.push(x[start_position..i].to_string());
+            parts.push(&x[start_position..i]);
             start_position = i + 1;
         }
     }
+    if start_position < x.len() {
+        // last part
+        parts.push(&x[start_position..]);
+    }
     parts
 }
 
-/// Parses bracketed information for positional information.
-/// Used in [parse_positional_info]
-fn parse_bracketed_info(x: &str) -> result::Result<Vec<i32>, ParseIntError> {
-    let parts = x.trim_start_matches('[').trim_end_matches(']').split('/');
-    let collected = parts
-        .into_iter()
-        .map(|p| p.parse::<i32>())
-        .collect::<result::Result<Vec<i32>, ParseIntError>>()?;
-    Ok(collected)
-}
-
 /// Positional Information type options
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PositionalInfoType {
     A,
     B,
@@ -288,7 +758,7 @@ fn categorize_positional_info(
     has_brackets: bool,
     has_comma_inside_brackets: bool,
     has_comma_outside_brackets: bool,
-) -> Result<PositionalInfoType> {
+) -> result::Result<PositionalInfoType, ValueError> {
     if !has_comma_outside_brackets && !has_comma_inside_brackets {
         Ok(PositionalInfoType::A)
     } else if !has_brackets && has_comma_outside_brackets {
@@ -298,8 +768,8 @@ fn categorize_positional_info(
     } else if has_brackets && has_comma_outside_brackets && has_comma_inside_brackets {
         Ok(PositionalInfoType::D)
     } else {
-        println!("could not parse positional information.");
-        Err(Box::new(ValueError))
+        log::debug!("could not parse positional information.");
+        Err(ValueError)
     }
 }
 
@@ -325,67 +795,270 @@ impl Position {
     }
 }
 
-/// Simple utility function to check whether
-pub fn check_parts(parts: &[&str]) -> Result<()> {
-    if parts.len() != 2 {
-        return Err(Box::new(ValueError));
+impl Display for Position {
+    /// Writes the position as `start/length`, without any brackets.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.start, self.length)
     }
-    Ok(())
 }
 
-pub fn parse_position_parts(position_str: &str, case: PositionalInfoType) -> Result<Position> {
-    let parts = position_str.split('/').collect::<Vec<&str>>();
-    check_parts(&parts)?;
-    let p1 = parts[0].parse::<i32>()?;
-    let p2 = parts[1].parse::<i32>()?;
-    Ok(Position::new(p1, p2, case))
+/// A single contiguous piece of matched text.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct Span {
+    /// Start position
+    pub start: i32,
+    /// Length of matched text
+    pub length: i32,
 }
 
-/// Parses out a Vector of [`Position`] types from a string reference.
-fn parse_positional_info(info: &str) -> Result<Vec<Position>> {
-    let tags = tag_pos_info(info);
-    let category = categorize_positional_info(tags.0, tags.1, tags.2)?;
-    let mut positions: Vec<Position> = Vec::new();
-    match category {
-        PositionalInfoType::A => {
-            for section in info.split(';') {
-                let p = parse_position_parts(section, PositionalInfoType::A)?;
-                positions.push(p);
+impl Span {
+    /// Initialize new span.
+    pub fn new(start: i32, length: i32) -> Span {
+        Span { start, length }
+    }
+}
+
+impl Display for Span {
+    /// Writes the span as `start/length`, without any brackets.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.start, self.length)
+    }
+}
+
+/// One occurrence of the concept in the text.
+///
+/// Multi-word concepts matched discontiguously (cases B and D) have one
+/// span per matched piece of text, all other occurrences have a single span.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct Occurrence {
+    /// Index of the `;`-separated group the occurrence was listed in. MetaMap
+    /// writes one group per trigger, in the order of the triggers, so the
+    /// section an occurrence was found in is that of its trigger (see
+    /// [`MmiOutput::occurrence_locations`]).
+    pub group: usize,
+    /// Spans making up this occurrence, in the order MetaMap reported them
+    pub spans: Vec<Span>,
+}
+
+impl Occurrence {
+    /// Initialize new occurrence.
+    pub fn new(group: usize, spans: Vec<Span>) -> Occurrence {
+        Occurrence { group, spans }
+    }
+}
+
+/// Positional information of a concept, grouped exactly as MetaMap reported it.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct PositionalInfo {
+    /// Layout of the field, see [`PositionalInfoType`]
+    pub case: PositionalInfoType,
+    /// Occurrences of the concept in the text
+    pub occurrences: Vec<Occurrence>,
+}
+
+impl PositionalInfo {
+    /// Builds positional information from occurrences that were not read from
+    /// an MMI record, picking the layout MetaMap would have used for them.
+    ///
+    /// Occurrences are ordered by group, as the layout lists groups in order.
+    /// Occurrences are only bracketed (cases C and D) when a group holds
+    /// several of them, since unbracketed spans of one group are read back as
+    /// a single occurrence.
+    pub fn from_occurrences(mut occurrences: Vec<Occurrence>) -> PositionalInfo {
+        occurrences.sort_by_key(|o| o.group);
+        let shared_group = occurrences.windows(2).any(|w| w[0].group == w[1].group);
+        let multiple_spans = occurrences.iter().any(|o| o.spans.len() > 1);
+        let case = match (shared_group, multiple_spans) {
+            (false, false) => PositionalInfoType::A,
+            (false, true) => PositionalInfoType::B,
+            (true, false) => PositionalInfoType::C,
+            (true, true) => PositionalInfoType::D,
+        };
+        PositionalInfo { case, occurrences }
+    }
+
+    /// Flattens the occurrences into a single list of [`Position`]s,
+    /// discarding which occurrence each span belonged to.
+    pub fn positions(&self) -> Vec<Position> {
+        self.occurrences
+            .iter()
+            .flat_map(|o| o.spans.iter())
+            .map(|s| Position::new(s.start, s.length, self.case))
+            .collect()
+    }
+}
+
+impl Display for PositionalInfo {
+    /// Writes positional information back into the layout it was parsed from.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bracketed = matches!(self.case, PositionalInfoType::C | PositionalInfoType::D);
+        for (i, occurrence) in self.occurrences.iter().enumerate() {
+            if i > 0 {
+                if occurrence.group == self.occurrences[i - 1].group {
+                    write!(f, ",")?;
+                } else {
+                    write!(f, ";")?;
+                }
             }
-            Ok(positions)
-        }
-        PositionalInfoType::B => {
-            for section in info.split(';') {
-                for subsection in section.split(',') {
-                    let p = parse_position_parts(subsection, PositionalInfoType::B)?;
-                    positions.push(p);
+            if bracketed {
+                write!(f, "[")?;
+            }
+            for (j, span) in occurrence.spans.iter().enumerate() {
+                if j > 0 {
+                    write!(f, ",")?;
                 }
+                write!(f, "{}", span)?;
+            }
+            if bracketed {
+                write!(f, "]")?;
             }
-            Ok(positions)
         }
-        PositionalInfoType::C => {
-            for section in info.split(';') {
-                for subsection in section.split(',') {
-                    let parts = parse_bracketed_info(subsection)?;
-                    let p = Position::new(parts[0], parts[1], PositionalInfoType::C);
-                    positions.push(p);
-                }
+        Ok(())
+    }
+}
+
+/// Whether an occurrence could have been produced by a trigger with `text`.
+///
+/// Each span of a multi-span occurrence is one word of the trigger text,
+/// so the text length is the span lengths plus the single spaces between them.
+fn occurrence_matches_text(occurrence: &Occurrence, text: &str) -> bool {
+    let matched: i64 = occurrence.spans.iter().map(|s| i64::from(s.length)).sum();
+    let gaps = occurrence.spans.len().saturating_sub(1) as i64;
+    text.chars().count() as i64 == matched + gaps
+}
+
+/// Assigns each occurrence to the index of the trigger that produced it.
+///
+/// MetaMap writes the positional information of each trigger as one `;`-separated
+/// group, in the order of the triggers, so when there are as many groups as
+/// triggers they pair up one-to-one. Otherwise the occurrences are still listed
+/// in trigger order, with a trigger whose text appears several times owning
+/// consecutive occurrences: as many occurrences as triggers pair up one-to-one,
+/// and failing that each occurrence goes to the next trigger (in order) whose
+/// text fits it, staying with the current trigger when it fits and enough
+/// occurrences remain for the rest.
+fn assign_occurrences(texts: &[&str], occurrences: &[Occurrence]) -> Vec<usize> {
+    if texts.is_empty() {
+        return Vec::new();
+    }
+    let mut groups = occurrences.iter().map(|o| o.group).collect::<Vec<_>>();
+    groups.sort_unstable();
+    groups.dedup();
+    if groups.len() == texts.len() {
+        return occurrences
+            .iter()
+            .map(|o| groups.binary_search(&o.group).unwrap_or(0))
+            .collect();
+    }
+    if texts.len() == occurrences.len() {
+        return (0..texts.len()).collect();
+    }
+    let mut assigned = Vec::with_capacity(occurrences.len());
+    let mut current = 0;
+    for (i, occurrence) in occurrences.iter().enumerate() {
+        // every occurrence after the first has already been given to `current`
+        let current_has_occurrence = i > 0;
+        let remaining_occurrences = occurrences.len() - i;
+        let remaining_triggers = texts.len() - current - 1;
+        let fits_current = occurrence_matches_text(occurrence, texts[current]);
+        let stay =
+            fits_current && (!current_has_occurrence || remaining_occurrences > remaining_triggers);
+        if !stay {
+            let next =
+                (current + 1..texts.len()).find(|&j| occurrence_matches_text(occurrence, texts[j]));
+            if let Some(j) = next {
+                current = j;
+            } else if !fits_current && current_has_occurrence && current + 1 < texts.len() {
+                current += 1;
             }
-            Ok(positions)
         }
-        PositionalInfoType::D => {
-            for section in info.split(';') {
-                for subsection in split_with_bracket_context(section) {
-                    for underground in subsection.split(',') {
-                        let parts = parse_bracketed_info(underground)?;
-                        let p = Position::new(parts[0], parts[1], PositionalInfoType::D);
-                        positions.push(p);
-                    }
+        assigned.push(current);
+    }
+    assigned
+}
+
+/// Simple utility function to check whether a split position has exactly two parts.
+pub(crate) fn check_parts(parts: &[&str]) -> result::Result<(), ValueError> {
+    if parts.len() != 2 {
+        return Err(ValueError);
+    }
+    Ok(())
+}
+
+/// Parses a single `start/length` pair into a [`Span`].
+///
+/// `column` is the byte column at which `span_str` starts within the record
+/// and is used to locate errors.
+fn parse_span(span_str: &str, column: usize) -> Result<Span> {
+    let parts = span_str.split('/').collect::<Vec<&str>>();
+    check_parts(&parts).map_err(|_| ParseError::InvalidValue {
+        field: "positional_info",
+        value: span_str.to_string(),
+        column,
+    })?;
+    let parse_int = |part: &str| {
+        part.parse::<i32>().map_err(|_| ParseError::InvalidInt {
+            field: "positional_info",
+            value: part.to_string(),
+            column: column + offset_in(span_str, part),
+        })
+    };
+    let p1 = parse_int(parts[0])?;
+    let p2 = parse_int(parts[1])?;
+    Ok(Span::new(p1, p2))
+}
+
+/// Parses a single `start/length` pair into a [`Position`].
+///
+/// Error columns are relative to the start of `position_str`.
+pub fn parse_position_parts(position_str: &str, case: PositionalInfoType) -> Result<Position> {
+    let span = parse_span(position_str, 0)?;
+    Ok(Position::new(span.start, span.length, case))
+}
+
+/// Parses out [`PositionalInfo`] from a string reference.
+///
+/// `column` is the byte column at which `info` starts within the record
+/// and is used to locate errors.
+fn parse_positional_info(info: &str, column: usize) -> Result<PositionalInfo> {
+    // records projected from formats without positions have none
+    if info.is_empty() {
+        return Ok(PositionalInfo::from_occurrences(Vec::new()));
+    }
+    let tags = tag_pos_info(info);
+    let case = categorize_positional_info(tags.0, tags.1, tags.2).map_err(|_| {
+        ParseError::InvalidValue {
+            field: "positional_info",
+            value: info.to_string(),
+            column,
+        }
+    })?;
+    let col = |part: &str| column + offset_in(info, part);
+    let mut occurrences: Vec<Occurrence> = Vec::new();
+    for (i, group) in info.split(';').enumerate() {
+        match case {
+            // one occurrence per group, with one or more spans
+            PositionalInfoType::A | PositionalInfoType::B => {
+                let spans = group
+                    .split(',')
+                    .map(|span| parse_span(span, col(span)))
+                    .collect::<Result<Vec<Span>>>()?;
+                occurrences.push(Occurrence::new(i, spans));
+            }
+            // one or more bracketed occurrences per group, with one or more spans
+            PositionalInfoType::C | PositionalInfoType::D => {
+                for bracketed in split_with_bracket_context(group) {
+                    let inner = bracketed.trim_start_matches('[').trim_end_matches(']');
+                    let spans = inner
+                        .split(',')
+                        .map(|span| parse_span(span, col(span)))
+                        .collect::<Result<Vec<Span>>>()?;
+                    occurrences.push(Occurrence::new(i, spans));
                 }
             }
-            Ok(positions)
         }
     }
+    Ok(PositionalInfo { case, occurrences })
 }
 
 /// Main struct for entire library.
@@ -393,7 +1066,7 @@ fn parse_positional_info(info: &str) -> Result<Vec<Position>> {
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct MmiOutput {
     /// unique identifier
-    pub id: String,
+    pub id: DocumentId,
     /// always MMI
     pub mmi: String,
     /// score of concept relevance, 0-1000, 1000 being perfect
@@ -401,7 +1074,7 @@ pub struct MmiOutput {
     /// name of the concept matched
     pub name: String,
     /// CUI for identified UMLS concept
-    pub cui: String,
+    pub cui: Cui,
     /// Semantic Type abbreviations
     pub semantic_types: Vec<String>,
     /// Triggers for MMI to flag this concept
@@ -409,9 +1082,9 @@ pub struct MmiOutput {
     /// Location of concept
     pub location: Location,
     /// Positional information of concept
-    pub positional_info: Vec<Position>,
+    pub positional_info: PositionalInfo,
     /// Optional MeSH [tree code(s)](https://www.nlm.nih.gov/mesh/meshhome.html)
-    pub tree_codes: Option<Vec<String>>,
+    pub tree_codes: Option<Vec<TreeCode>>,
 }
 
 impl MmiOutput {
@@ -423,36 +1096,135 @@ impl MmiOutput {
     /// [`parse_record`] will probably be **much** more practical since it
     /// accepts a string reference and does the field tagging/mapping for you.
     pub fn assemble(parts: HashMap<&str, &str>) -> Result<Self> {
-        // does not use `parts.get(<key>)` because WE made the keys so WE
-        // know they exist
-        let id = parts["id"].to_string();
-        let mmi = parts["mmi"].to_string();
-        let score = parts["score"].parse::<f64>()?;
-        let name = parts["name"].to_string();
-        let cui = parts["cui"].to_string();
-        let source_sem_types = parts["semantic_types"].to_string();
-        let semantic_types = parse_semantic_types(&source_sem_types);
-        let source_triggers = parts["triggers"].to_string();
-        let triggers = parse_triggers(&source_triggers)?;
-        let source_location = parts["location"].to_string();
-        let location = Location::from_str(&source_location)?;
-        let source_positions = parts["positional_info"].to_string();
-        let positional_info = parse_positional_info(&source_positions)?;
-        let source_tree_codes = parts["tree_codes"].to_string();
-        let tree_codes = parse_tree_codes(&source_tree_codes);
-        let mmi_output = MmiOutput {
-            id,
-            mmi,
-            score,
-            name,
-            cui,
-            semantic_types,
-            triggers,
-            location,
-            positional_info,
-            tree_codes,
-        };
-        Ok(mmi_output)
+        // the map may have been built by hand, so a missing key is an error
+        // rather than a panic
+        let fields = ordered_fields(&parts, MMI_FIELDS)?;
+        let mut warnings = Warnings::new(Strictness::Lenient);
+        Ok(MmiOutputRef::from_fields(fields, Dialect::MetaMap, &mut warnings)?.into_owned())
+    }
+
+    /// Flat list of every [`Position`] of the concept.
+    /// See [`PositionalInfo::positions`].
+    pub fn positions(&self) -> Vec<Position> {
+        self.positional_info.positions()
+    }
+
+    /// Index of the trigger that produced each occurrence, index-aligned with
+    /// the occurrences of [`MmiOutput::positional_info`] (empty without triggers).
+    fn occurrence_triggers(&self) -> Vec<usize> {
+        let texts = self
+            .triggers
+            .iter()
+            .map(|t| t.text.as_str())
+            .collect::<Vec<_>>();
+        assign_occurrences(&texts, &self.positional_info.occurrences)
+    }
+
+    /// The occurrences each trigger produced, index-aligned with [`MmiOutput::triggers`].
+    ///
+    /// MMI output does not link triggers to positions directly. MetaMap writes
+    /// one `;`-separated group of positions per trigger, in trigger order, which
+    /// pairs them up when the counts agree. Otherwise the alignment relies on
+    /// both being listed in the same order, using the trigger text length to
+    /// decide where one trigger's occurrences end.
+    pub fn trigger_spans(&self) -> Vec<Vec<&Occurrence>> {
+        let mut spans = vec![Vec::new(); self.triggers.len()];
+        let assigned = self.occurrence_triggers();
+        for (occurrence, trigger) in self.positional_info.occurrences.iter().zip(assigned) {
+            spans[trigger].push(occurrence);
+        }
+        spans
+    }
+
+    /// The location (`loc`) of the trigger that produced each occurrence,
+    /// index-aligned with the occurrences of [`MmiOutput::positional_info`].
+    /// `None` when the record has no triggers.
+    ///
+    /// ```rust
+    /// use mmi_parser::{parse_record, Output};
+    ///
+    /// let line = "24119710|MMI|637.30|Isopoda|C0598806|[euka]|[\"Isopod\"-ab-1-\"isopod\"-adj-0,\"Isopoda\"-ti-1-\"Isopoda\"-noun-0]|TI;AB|228/6;136/7|";
+    /// let mmi = match parse_record(line).unwrap() {
+    ///     Output::MMI(mmi) => mmi,
+    ///     _ => unreachable!(),
+    /// };
+    /// let locations = mmi.occurrence_locations();
+    /// assert_eq!(locations[0].unwrap().to_string(), "AB");
+    /// assert_eq!(locations[1].unwrap().to_string(), "TI");
+    /// ```
+    pub fn occurrence_locations(&self) -> Vec<Option<&Location>> {
+        let mut assigned = self.occurrence_triggers().into_iter();
+        self.positional_info
+            .occurrences
+            .iter()
+            .map(|_| assigned.next().map(|trigger| &self.triggers[trigger].loc))
+            .collect()
+    }
+
+    /// Looks up each of [`MmiOutput::semantic_types`] in the built-in table,
+    /// index-aligned with it. Unknown abbreviations are `None`.
+    pub fn semantic_type_details(&self) -> Vec<Option<&'static SemanticType>> {
+        self.semantic_types
+            .iter()
+            .map(|abbreviation| SemanticType::from_abbreviation(abbreviation))
+            .collect()
+    }
+
+    /// The distinct top-level MeSH codes (e.g. `B01`) the record's tree codes
+    /// fall under, in order of first appearance.
+    pub fn mesh_branches(&self) -> impl Iterator<Item = TreeCode> + '_ {
+        let mut seen = Vec::new();
+        self.tree_codes
+            .iter()
+            .flatten()
+            .map(TreeCode::root)
+            .filter(move |root| {
+                let new = !seen.contains(root);
+                if new {
+                    seen.push(root.clone());
+                }
+                new
+            })
+    }
+
+    /// Writes the record back into a single line of fielded MMI output.
+    /// Equivalent to `to_string()`, see the [`Display`] implementation.
+    pub fn to_mmi_line(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for MmiOutput {
+    /// Writes the record as a pipe-delimited fielded MMI line.
+    ///
+    /// Scores are written with the two decimals MetaMap uses.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}|{}|{:.2}|{}|{}|[{}]|[",
+            self.id,
+            self.mmi,
+            self.score,
+            self.name,
+            self.cui,
+            self.semantic_types.join(",")
+        )?;
+        for (i, trigger) in self.triggers.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", trigger)?;
+        }
+        write!(f, "]|{}|{}|", self.location, self.positional_info)?;
+        if let Some(codes) = &self.tree_codes {
+            for (i, code) in codes.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ";")?;
+                }
+                write!(f, "{}", code)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -477,6 +1249,15 @@ impl FromStr for AbbreviationType {
     }
 }
 
+impl Display for AbbreviationType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AbbreviationType::AA => write!(f, "AA"),
+            AbbreviationType::UA => write!(f, "UA"),
+        }
+    }
+}
+
 /// Abbreviation and Acronym position information
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct AaPosInfo {
@@ -499,7 +1280,7 @@ impl AaPosInfo {
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct AaOutput {
     /// Unique identifier
-    pub id: String,
+    pub id: DocumentId,
     /// Abbreviation type: either MetaMap defined or User-defined
     pub abbreviation_type: AbbreviationType,
     /// Short form of the acronym/abbreviation
@@ -527,59 +1308,81 @@ impl AaOutput {
     /// [`parse_record`] will probably be **much** more practical since it
     /// accepts a string reference and does the field tagging/mapping for you.
     pub fn assemble(parts: HashMap<&str, &str>) -> Result<Self> {
-        // does not use `parts.get(<key>)` because WE made the keys so WE
-        // know they exist
-        let id = parts["id"].to_string();
-        let abbreviation_type = AbbreviationType::from_str(parts["abbreviation_type"])?;
-        let short_form = parts["short_form"].to_string();
-        let long_form = parts["long_form"].to_string();
-        let short_token_count = parts["short_token_count"].parse::<i32>()?;
-        let short_character_count = parts["short_character_count"].parse::<i32>()?;
-        let long_token_count = parts["long_token_count"].parse::<i32>()?;
-        let long_character_count = parts["long_character_count"].parse::<i32>()?;
-        let position_parts = parts["positional_info"].split(':').collect::<Vec<&str>>();
-        let pp1 = position_parts[0].parse::<i32>()?;
-        let pp2 = position_parts[1].parse::<i32>()?;
-        let positional_info = AaPosInfo::new(pp1, pp2);
-        let aa_output = AaOutput {
-            id,
-            abbreviation_type,
-            short_form,
-            long_form,
-            short_token_count,
-            short_character_count,
-            long_token_count,
-            long_character_count,
-            positional_info,
-        };
-        Ok(aa_output)
+        // the map may have been built by hand, so a missing key is an error
+        // rather than a panic
+        let fields = ordered_fields(&parts, AA_FIELDS)?;
+        Ok(AaOutputRef::from_fields(fields, &mut Warnings::new(Strictness::Lenient))?.into_owned())
+    }
+
+    /// Writes the record back into a single line of fielded MMI output.
+    /// Equivalent to `to_string()`, see the [`Display`] implementation.
+    pub fn to_mmi_line(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for AaOutput {
+    /// Writes the record as a pipe-delimited fielded AA line.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}|{}|{}|{}|{}|{}|{}|{}|{}:{}",
+            self.id,
+            self.abbreviation_type,
+            self.short_form,
+            self.long_form,
+            self.short_token_count,
+            self.short_character_count,
+            self.long_token_count,
+            self.long_character_count,
+            self.positional_info.start,
+            self.positional_info.length
+        )
     }
 }
 
 /// Labels AA records with the corresponding field names
 pub fn label_aa_parts(parts: Vec<&str>) -> Result<HashMap<&str, &str>> {
-    if parts.len() != 9 {
-        return Err(Box::new(ValueError));
-    }
-    let mut map: HashMap<&str, &str> = HashMap::new();
-    map.insert("id", parts[0]);
-    map.insert("abbreviation_type", parts[1]);
-    map.insert("short_form", parts[2]);
-    map.insert("long_form", parts[3]);
-    map.insert("short_token_count", parts[4]);
-    map.insert("short_character_count", parts[5]);
-    map.insert("long_token_count", parts[6]);
-    map.insert("long_character_count", parts[7]);
-    map.insert("positional_info", parts[8]);
-    Ok(map)
-}
-
-#[derive(Serialize, Deserialize, Debug)]
+    if parts.len() != AA_FIELDS.len() {
+        return Err(ParseError::FieldCount {
+            expected: AA_FIELDS.len(),
+            found: parts.len(),
+        });
+    }
+    Ok(AA_FIELDS.into_iter().zip(parts).collect())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub enum Output {
     MMI(MmiOutput),
     AA(AaOutput),
 }
 
+impl Output {
+    /// Id of the document the record belongs to.
+    pub fn id(&self) -> &DocumentId {
+        match self {
+            Output::MMI(x) => &x.id,
+            Output::AA(x) => &x.id,
+        }
+    }
+
+    /// Writes the record back into a single line of fielded MMI output.
+    /// Equivalent to `to_string()`, see the [`Display`] implementation.
+    pub fn to_mmi_line(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Output::MMI(x) => write!(f, "{}", x),
+            Output::AA(x) => write!(f, "{}", x),
+        }
+    }
+}
+
 /// A better alternative to [`MmiOutput::assemble`] or [`AaOutput::assemble`]
 /// Takes a string reference, splits it on vertical bar (pipe) characters,
 /// labels each item with its corresponding field name,
@@ -593,10 +1396,12 @@ pub enum Output {
 /// * text: a string reference representing a single line of MMI/AA output
 ///
 /// Returns:
-/// * Result<Output, ValueError>: An enumeration with MMI::MmiOutput and AA::AaOutput options. Could return
-/// error if a valid option is not found in the second vector position.
+/// * Result<Output, ParseError>: An enumeration with MMI::MmiOutput and AA::AaOutput options. Errors
+///   with a [`ParseError`] naming the field, value and column that could not be parsed.
 ///
 /// This effectively converts *each* fielded MMI **line** into an [`Output`] of either MMI or AA type.
+/// Malformed ids, CUIs and tree codes are kept as they are; use [`parse_record_with`] with
+/// [`Strictness::Strict`] to reject them.
 /// For example:
 ///
 /// ```rust
@@ -613,33 +1418,39 @@ pub enum Output {
 ///     let result = mmi_parser::parse_record(record.as_str());
 ///     println!("{:?}", result.unwrap()); // must use debug
 /// }
-
 /// ```
 pub fn parse_record(text: &str) -> Result<Output> {
-    let parts = split_text(text);
-    // only 2 valid length options, easy to stop early
-    if parts.len() != 10 && parts.len() != 9 {
-        return Err(Box::new(ValueError));
-    }
-    match parts[1].to_ascii_uppercase().as_str() {
-        "MMI" => {
-            let fields = label_mmi_parts(parts)?;
-            let output = MmiOutput::assemble(fields)?;
-            Ok(Output::MMI(output))
-        }
-        "AA" | "UA" => {
-            let fields = label_aa_parts(parts)?;
-            let output = AaOutput::assemble(fields)?;
-            Ok(Output::AA(output))
-        }
-        _ => Err(Box::new(ValueError)),
-    }
+    parse_record_ref(text).map(OutputRef::into_owned)
+}
+
+/// Like [`parse_record`], with a choice of how strictly identifiers are validated
+/// and with the problems found returned.
+///
+/// With [`Strictness::Lenient`] (what [`parse_record`] uses), a malformed `id`, `cui`
+/// or tree code does not fail the record; the error it would have caused is returned
+/// alongside it instead, and the malformed value is kept as it is. With
+/// [`Strictness::Strict`] the list of warnings is always empty.
+///
+/// ```rust
+/// use mmi_parser::{parse_record_with, Strictness};
+///
+/// let line = "24119710|MMI|170.37|Effect|C12805|[qlco]|[\"effects\"-ti-1-\"Effects\"-noun-0]|TI|21/7|";
+/// assert!(parse_record_with(line, Strictness::Strict).is_err());
+/// let (_record, warnings) = parse_record_with(line, Strictness::Lenient).unwrap();
+/// assert_eq!(warnings[0].field(), Some("cui"));
+/// ```
+pub fn parse_record_with(text: &str, strictness: Strictness) -> Result<(Output, Vec<ParseError>)> {
+    let mut warnings = Warnings::new(strictness);
+    let output = borrowed::parse_record_ref_with(text, &mut warnings)?.into_owned();
+    Ok((output, warnings.found))
 }
 
 #[cfg(test)]
 mod tests {
     use core::panic;
 
+    use proptest::prelude::*;
+
     use super::*;
 
     #[test]
@@ -706,7 +1517,7 @@ mod tests {
         let r = split_with_quote_context(sample, ',');
         assert_eq!(r.len(), 4);
         for x in r {
-            let r2 = split_with_quote_context(&x, '-');
+            let r2 = split_with_quote_context(x, '-');
             assert_eq!(6, r2.len()); // sextuple
         }
     }
@@ -756,20 +1567,54 @@ mod tests {
         let sample = "ti";
         assert_eq!(
             Location::from_str(sample.to_uppercase().as_str()).unwrap(),
-            Location::TI
+            Location::from(Section::TI)
         );
         let sample = "AB";
-        assert_eq!(Location::from_str(sample).unwrap(), Location::AB);
+        assert_eq!(
+            Location::from_str(sample).unwrap(),
+            Location::from(Section::AB)
+        );
         let sample = "TX";
-        assert_eq!(Location::from_str(sample).unwrap(), Location::TX);
+        assert_eq!(
+            Location::from_str(sample).unwrap(),
+            Location::from(Section::TX)
+        );
         let sample = "TI;AB";
-        assert_eq!(Location::from_str(sample).unwrap(), Location::Tiab);
+        assert_eq!(
+            Location::from_str(sample).unwrap(),
+            Location::new([Section::TI, Section::AB])
+        );
     }
     #[test]
     #[should_panic]
     fn test_invalid_location() {
-        let sample = "BG";
-        assert_eq!(Location::from_str(sample).unwrap(), Location::Tiab);
+        let sample = "TI;;AB";
+        Location::from_str(sample).unwrap();
+    }
+
+    #[test]
+    fn test_location_combinations() {
+        let loc = Location::from_str("tx;ti;bg;TI").unwrap();
+        assert_eq!(
+            loc.sections(),
+            [Section::TX, Section::TI, Section::Other("BG".to_string())]
+        );
+        assert_eq!(loc.to_string(), "TX;TI;BG");
+        assert_eq!(loc.legacy_name(), None);
+        assert_eq!(
+            Location::from_str("TI;AB").unwrap().legacy_name(),
+            Some("Tiab")
+        );
+        assert_eq!(Location::from_str("AB;TI").unwrap().legacy_name(), None);
+
+        let json = serde_json::to_string(&loc).unwrap();
+        assert_eq!(json, r#"["TX","TI","BG"]"#);
+        assert_eq!(serde_json::from_str::<Location>(&json).unwrap(), loc);
+        assert_eq!(
+            serde_json::from_str::<Location>(r#""Tiab""#).unwrap(),
+            Location::new([Section::TI, Section::AB])
+        );
+        assert!(serde_json::from_str::<Location>("[]").is_err());
     }
 
     #[test]
@@ -779,15 +1624,12 @@ mod tests {
         let sample = "B01.050.500.131.365.400";
         assert_eq!(
             parse_tree_codes(sample),
-            Some(vec![String::from("B01.050.500.131.365.400")])
+            Some(vec!["B01.050.500.131.365.400"])
         );
         let sample = "B01.050.500.131.365.400;B01.050.500.131.365.400";
         assert_eq!(
             parse_tree_codes(sample),
-            Some(vec![
-                "B01.050.500.131.365.400".to_string(),
-                "B01.050.500.131.365.400".to_string()
-            ])
+            Some(vec!["B01.050.500.131.365.400", "B01.050.500.131.365.400"])
         );
     }
 
@@ -795,7 +1637,7 @@ mod tests {
     fn test_parse_positional_info() {
         let sample = "228/6;136/7";
         assert_eq!(
-            parse_positional_info(sample).unwrap(),
+            parse_positional_info(sample, 0).unwrap().positions(),
             vec![
                 Position::new(228, 6, PositionalInfoType::A),
                 Position::new(136, 7, PositionalInfoType::A)
@@ -803,7 +1645,7 @@ mod tests {
         );
         let s1 = "[4061/10,4075/11],[4061/10,4075/11]";
         assert_eq!(
-            parse_positional_info(s1).unwrap(),
+            parse_positional_info(s1, 0).unwrap().positions(),
             vec![
                 Position::new(4061, 10, PositionalInfoType::D),
                 Position::new(4075, 11, PositionalInfoType::D),
@@ -813,7 +1655,7 @@ mod tests {
         );
         let s1 = "7059/5,7073/5";
         assert_eq!(
-            parse_positional_info(s1).unwrap(),
+            parse_positional_info(s1, 0).unwrap().positions(),
             vec![
                 Position::new(7059, 5, PositionalInfoType::B),
                 Position::new(7073, 5, PositionalInfoType::B),
@@ -821,7 +1663,7 @@ mod tests {
         );
         let s1 = "[1351/8],[1437/8]";
         assert_eq!(
-            parse_positional_info(s1).unwrap(),
+            parse_positional_info(s1, 0).unwrap().positions(),
             vec![
                 Position::new(1351, 8, PositionalInfoType::C),
                 Position::new(1437, 8, PositionalInfoType::C),
@@ -829,13 +1671,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_positional_info_grouping() {
+        let s1 = "[4061/10,4075/11],[4166/10,4180/11];[5000/3]";
+        assert_eq!(
+            parse_positional_info(s1, 0).unwrap(),
+            PositionalInfo {
+                case: PositionalInfoType::D,
+                occurrences: vec![
+                    Occurrence::new(0, vec![Span::new(4061, 10), Span::new(4075, 11)]),
+                    Occurrence::new(0, vec![Span::new(4166, 10), Span::new(4180, 11)]),
+                    Occurrence::new(1, vec![Span::new(5000, 3)]),
+                ],
+            }
+        );
+        let s1 = "7059/5,7073/5;7100/5";
+        assert_eq!(
+            parse_positional_info(s1, 0).unwrap().occurrences,
+            vec![
+                Occurrence::new(0, vec![Span::new(7059, 5), Span::new(7073, 5)]),
+                Occurrence::new(1, vec![Span::new(7100, 5)]),
+            ]
+        );
+    }
+
     #[test]
     fn test_new_trigger() {
         let t = ("hi", "tI;aB", "124", "fun times", "testing stuff", true);
-        let tt = Trigger::new(t.0, t.1, t.2, t.3, t.4, t.5);
+        let tt = Trigger::new(t.0, t.1, t.2, t.3, t.4, t.5).unwrap();
         let actual_tt = Trigger {
             name: String::from("hi"),
-            loc: Location::Tiab,
+            loc: Location::new([Section::TI, Section::AB]),
             loc_position: 124,
             text: "fun times".to_string(),
             part_of_speech: "testing stuff".to_string(),
@@ -847,12 +1713,16 @@ mod tests {
     #[test]
     fn test_parse_triggers() {
         let sample = "[\"Crustacea\"-ti-1-\"Crustacea\"-noun-0]";
-        let result = parse_triggers(sample);
+        let result = parse_triggers(sample, 0);
         assert_eq!(
-            result.unwrap(),
+            result
+                .unwrap()
+                .into_iter()
+                .map(TriggerRef::into_owned)
+                .collect::<Vec<_>>(),
             [Trigger {
                 name: "Crustacea".to_string(),
-                loc: Location::TI,
+                loc: Location::from(Section::TI),
                 loc_position: 1,
                 text: "Crustacea".to_string(),
                 part_of_speech: "noun".to_string(),
@@ -860,7 +1730,7 @@ mod tests {
             }]
         );
         let s2 = "[\"Crustacea\"-ti-1-\"Crustacea\"-noun";
-        assert!(parse_triggers(s2).is_err());
+        assert!(parse_triggers(s2, 0).is_err());
     }
 
     #[test]
@@ -880,16 +1750,16 @@ mod tests {
         map.insert("positional_info", "228/6;136/7");
         map.insert("tree_codes", "B01.050.500.131.365.400");
         let expected = MmiOutput {
-            id: "24119710".to_string(),
+            id: DocumentId::from_str("24119710").unwrap(),
             mmi: "MMI".to_string(),
             score: 637.30,
             name: "Isopoda".to_string(),
-            cui: "C0598806".to_string(),
+            cui: Cui::from_str("C0598806").unwrap(),
             semantic_types: vec!["euka".to_string()],
             triggers: vec![
                 Trigger {
                     name: "Isopod".to_string(),
-                    loc: Location::AB,
+                    loc: Location::from(Section::AB),
                     loc_position: 1,
                     text: "isopod".to_string(),
                     part_of_speech: "adj".to_string(),
@@ -897,27 +1767,22 @@ mod tests {
                 },
                 Trigger {
                     name: "Isopoda".to_string(),
-                    loc: Location::TI,
+                    loc: Location::from(Section::TI),
                     loc_position: 1,
                     text: "Isopoda".to_string(),
                     part_of_speech: "noun".to_string(),
                     negation: false,
                 },
             ],
-            location: Location::Tiab,
-            positional_info: vec![
-                Position {
-                    start: 228,
-                    length: 6,
-                    case: PositionalInfoType::A,
-                },
-                Position {
-                    start: 136,
-                    length: 7,
-                    case: PositionalInfoType::A,
-                },
-            ],
-            tree_codes: Some(vec!["B01.050.500.131.365.400".to_string()]),
+            location: Location::new([Section::TI, Section::AB]),
+            positional_info: PositionalInfo {
+                case: PositionalInfoType::A,
+                occurrences: vec![
+                    Occurrence::new(0, vec![Span::new(228, 6)]),
+                    Occurrence::new(1, vec![Span::new(136, 7)]),
+                ],
+            },
+            tree_codes: Some(vec![TreeCode::from_str("B01.050.500.131.365.400").unwrap()]),
         };
         assert_eq!(expected, MmiOutput::assemble(map).unwrap());
     }
@@ -926,16 +1791,16 @@ mod tests {
     fn test_parse_mmi_for_mmi() {
         let s1 = "3124119710|MMI|637.30|Isopoda|C0598806|[euka]|[\"Isopod\"-ab-1-\"isopod\"-adj-0,\"Isopoda\"-ti-1-\"Isopoda\"-noun-0]|TI;AB|228/6;136/7|B01.050.500.131.365.400";
         let expected = MmiOutput {
-            id: "3124119710".to_string(),
+            id: DocumentId::from_str("3124119710").unwrap(),
             mmi: "MMI".to_string(),
             score: 637.3,
             name: "Isopoda".to_string(),
-            cui: "C0598806".to_string(),
+            cui: Cui::from_str("C0598806").unwrap(),
             semantic_types: vec!["euka".to_string()],
             triggers: vec![
                 Trigger {
                     name: "Isopod".to_string(),
-                    loc: Location::AB,
+                    loc: Location::from(Section::AB),
                     loc_position: 1,
                     text: "isopod".to_string(),
                     part_of_speech: "adj".to_string(),
@@ -943,27 +1808,22 @@ mod tests {
                 },
                 Trigger {
                     name: "Isopoda".to_string(),
-                    loc: Location::TI,
+                    loc: Location::from(Section::TI),
                     loc_position: 1,
                     text: "Isopoda".to_string(),
                     part_of_speech: "noun".to_string(),
                     negation: false,
                 },
             ],
-            location: Location::Tiab,
-            positional_info: vec![
-                Position {
-                    start: 228,
-                    length: 6,
-                    case: PositionalInfoType::A,
-                },
-                Position {
-                    start: 136,
-                    length: 7,
-                    case: PositionalInfoType::A,
-                },
-            ],
-            tree_codes: Some(vec!["B01.050.500.131.365.400".to_string()]),
+            location: Location::new([Section::TI, Section::AB]),
+            positional_info: PositionalInfo {
+                case: PositionalInfoType::A,
+                occurrences: vec![
+                    Occurrence::new(0, vec![Span::new(228, 6)]),
+                    Occurrence::new(1, vec![Span::new(136, 7)]),
+                ],
+            },
+            tree_codes: Some(vec![TreeCode::from_str("B01.050.500.131.365.400").unwrap()]),
         };
         let parsed = match parse_record(s1).unwrap() {
             Output::MMI(x) => x,
@@ -1004,7 +1864,7 @@ mod tests {
 
     #[test]
     fn test_parse_bracketed_info() {
-        let t = parse_bracketed_info("[12/hi]");
+        let t = parse_positional_info("[12/hi]", 0);
         assert!(t.is_err());
     }
 
@@ -1014,6 +1874,14 @@ mod tests {
         assert!(check_parts(&["hi", "bye", "see ya"]).is_err());
     }
 
+    #[test]
+    fn test_parse_position_parts() {
+        let position = parse_position_parts("228/6", PositionalInfoType::A).unwrap();
+        assert_eq!(position, Position::new(228, 6, PositionalInfoType::A));
+        let err = parse_position_parts("228/x", PositionalInfoType::A).unwrap_err();
+        assert_eq!(err.column(), Some(4));
+    }
+
     #[test]
     fn test_label_aa_parts() {
         let sample = vec!["hi", "by", "se", "yA", "later", "alligator"];
@@ -1024,4 +1892,426 @@ mod tests {
     fn test_parse_record_fail() {
         assert!(parse_record("hi").is_err());
     }
+
+    #[test]
+    fn test_new_trigger_invalid() {
+        assert!(matches!(
+            Trigger::new("hi", "", "124", "fun times", "noun", true),
+            Err(ParseError::UnknownLocation { .. })
+        ));
+        assert!(matches!(
+            Trigger::new("hi", "ti", "1x", "fun times", "noun", true),
+            Err(ParseError::InvalidInt { .. })
+        ));
+    }
+
+    #[test]
+    fn test_assemble_missing_field() {
+        assert_eq!(
+            MmiOutput::assemble(HashMap::new()).unwrap_err(),
+            ParseError::MissingField { field: "id" }
+        );
+        let mut map = HashMap::new();
+        map.insert("id", "23074487");
+        map.insert("abbreviation_type", "AA");
+        assert_eq!(
+            AaOutput::assemble(map).unwrap_err(),
+            ParseError::MissingField {
+                field: "short_form"
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_error_field_count() {
+        assert_eq!(
+            parse_record("hi").unwrap_err(),
+            ParseError::FieldCount {
+                expected: 10,
+                found: 1
+            }
+        );
+        assert_eq!(
+            parse_record("23074487|AA|FY|fiscal years|1|2|3|12").unwrap_err(),
+            ParseError::FieldCount {
+                expected: 9,
+                found: 8
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_error_locations() {
+        let s1 = "24119710|MMI|abc|Effect|C1280500|[qlco]|[\"effects\"-ti-1-\"Effects\"-noun-0]|TI|21/7|";
+        let err = parse_record(s1).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::InvalidFloat {
+                field: "score",
+                value: "abc".to_string(),
+                column: 13
+            }
+        );
+        assert_eq!(&s1[err.column().unwrap()..][..3], "abc");
+
+        let s1 = "24119710|MMI|170.37|Effect|C1280500|[qlco]|[\"effects\"-ti-x-\"Effects\"-noun-0]|TI|21/7|";
+        let err = parse_record(s1).unwrap_err();
+        assert_eq!(err.field(), Some("triggers"));
+        assert_eq!(err.value(), Some("x"));
+        assert!(matches!(err, ParseError::InvalidInt { .. }));
+        assert_eq!(&s1[err.column().unwrap()..][..1], "x");
+
+        let s1 = "24119710|MMI|170.37|Effect|C1280500|[qlco]|[\"effects\"-ti;-1-\"Effects\"-noun-0]|TI|21/7|";
+        let err = parse_record(s1).unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::UnknownLocation {
+                field: "triggers",
+                ..
+            }
+        ));
+        assert_eq!(&s1[err.column().unwrap()..][..3], "ti;");
+
+        let s1 = "24119710|MMI|170.37|Effect|C1280500|[qlco]|[\"effects\"-ti-1-\"Effects\"-noun-0]|TI;;AB|21/7|";
+        let err = parse_record(s1).unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::UnknownLocation {
+                field: "location",
+                ..
+            }
+        ));
+        assert_eq!(&s1[err.column().unwrap()..][..6], "TI;;AB");
+
+        let s1 = "24119710|MMI|170.37|Effect|C1280500|[qlco]|[\"effects\"-ti-1-\"Effects\"-noun]|TI|21/7|";
+        let err = parse_record(s1).unwrap_err();
+        assert!(matches!(err, ParseError::MalformedTrigger { parts: 5, .. }));
+        assert_eq!(err.column(), Some(43));
+
+        let s1 = "23074497|MMI|7.59|Transplant Recipients|C0376387|[podg]|[\"Transplant Recipients\"-ab-27-\"recipients transplants\"-noun-0]|AB|[4061/10,4075/11],[4166/10,41x0/11]|M01.925";
+        let err = parse_record(s1).unwrap_err();
+        assert_eq!(err.field(), Some("positional_info"));
+        assert_eq!(err.value(), Some("41x0"));
+        assert_eq!(&s1[err.column().unwrap()..][..4], "41x0");
+
+        let s1 = "23074487|AA|FY|fiscal years|1|2|3|12|9362:y";
+        let err = parse_record(s1).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::InvalidInt {
+                field: "positional_info",
+                value: "y".to_string(),
+                column: 42
+            }
+        );
+
+        let s1 = "23074487|XX|FY|fiscal years|1|2|3|12|9362:2";
+        let err = parse_record(s1).unwrap_err();
+        assert_eq!(err.field(), Some("record_type"));
+        assert_eq!(err.column(), Some(9));
+    }
+
+    const SAMPLES: [&str; 6] = [
+        "3124119710|MMI|637.30|Isopoda|C0598806|[euka]|[\"Isopod\"-ab-1-\"isopod\"-adj-0,\"Isopoda\"-ti-1-\"Isopoda\"-noun-0]|TI;AB|228/6;136/7|B01.050.500.131.365.400",
+        "5538822|MMI|2.05|Basal Cell|C0596155|[cell]|[\"basal cells\"-ab-41-\"basal cells\"-noun-0]|AB|7059/5,7073/5|",
+        "5099124|MMI|2.51|Dominant|C1527180|[ftcn]|[\"Dominant\"-ab-7-\"dominant\"-adj-0]|AB|[1351/8],[1437/8]|",
+        "23074497|MMI|7.59|Transplant Recipients|C0376387|[podg]|[\"Transplant Recipients\"-ab-27-\"recipients transplants\"-noun-0]|AB|[4061/10,4075/11],[4166/10,4180/11]|M01.925",
+        "23074487|AA|FY|fiscal years|1|2|3|12|9362:2",
+        "23074399|UA|DORs|diagnostic odds ratios|1|4|5|22|8926:4",
+    ];
+
+    /// Checks that a parse error points at where its value actually is in the line.
+    fn assert_error_located(
+        line: &str,
+        err: &ParseError,
+    ) -> std::result::Result<(), TestCaseError> {
+        if let (Some(column), Some(value)) = (err.column(), err.value()) {
+            prop_assert!(line.is_char_boundary(column));
+            prop_assert!(line[column..].starts_with(value));
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn parse_record_never_panics(line in "\\PC*") {
+            if let Err(err) = parse_record(&line) {
+                assert_error_located(&line, &err)?;
+            }
+        }
+
+        #[test]
+        fn parse_record_never_panics_on_near_records(
+            kind in prop_oneof![Just("MMI".to_string()), Just("AA".to_string()), Just("ua".to_string()), "[a-zA-Z]{0,3}"],
+            mut fields in prop::collection::vec("[\\[\\]\"\\-,;:/.0-9a-zA-Zé ]{0,16}", 7..=9),
+        ) {
+            fields.insert(1, kind);
+            let line = fields.join("|");
+            if let Err(err) = parse_record(&line) {
+                assert_error_located(&line, &err)?;
+            }
+        }
+
+        #[test]
+        fn parse_record_never_panics_on_mutated_samples(
+            sample in prop::sample::select(SAMPLES.to_vec()),
+            start in any::<prop::sample::Index>(),
+            len in 0usize..8,
+            insert in "\\PC{0,8}",
+        ) {
+            let start = start.index(sample.len() + 1);
+            let end = (start + len).min(sample.len());
+            let line = format!("{}{}{}", &sample[..start], insert, &sample[end..]);
+            if let Err(err) = parse_record(&line) {
+                assert_error_located(&line, &err)?;
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_samples() {
+        for path in ["data/MMI_sample.txt", "data/AA_sample.txt"] {
+            let contents = std::fs::read_to_string(path).unwrap();
+            for line in contents.lines().filter(|line| !line.is_empty()) {
+                let parsed = parse_record(line).unwrap();
+                assert_eq!(parsed.to_mmi_line(), line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_display_positional_layouts() {
+        let base = "5538822|MMI|2.05|Basal Cell|C0596155|[cell]|[\"basal cells\"-ab-41-\"basal cells\"-noun-1]|AB|";
+        for positions in [
+            "7059/5",
+            "228/6;136/7",
+            "7059/5,7073/5",
+            "7059/5,7073/5;7100/5",
+            "[1351/8],[1437/8]",
+            "[1351/8];[1437/8],[1500/8]",
+            "[4061/10,4075/11],[4166/10,4180/11]",
+            "[4061/10,4075/11],[4166/10,4180/11];[5000/3,5010/4],[5100/3,5110/4]",
+        ] {
+            for codes in ["", "B01.050;M01.925"] {
+                let line = format!("{}{}|{}", base, positions, codes);
+                assert_eq!(parse_record(&line).unwrap().to_mmi_line(), line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_occurrences_round_trip() {
+        let base = "5538822|MMI|2.05|Basal Cell|C0596155|[cell]|[\"basal cells\"-ab-41-\"basal cells\"-noun-1]|TI;AB|";
+        let cases = [
+            (vec![(0, vec![(1, 2)])], PositionalInfoType::A),
+            (
+                vec![(0, vec![(1, 2)]), (1, vec![(9, 2)])],
+                PositionalInfoType::A,
+            ),
+            (vec![(0, vec![(1, 2), (5, 2)])], PositionalInfoType::B),
+            (
+                vec![(0, vec![(1, 2), (5, 2)]), (1, vec![(9, 2)])],
+                PositionalInfoType::B,
+            ),
+            (
+                vec![(0, vec![(1, 2)]), (0, vec![(5, 2)])],
+                PositionalInfoType::C,
+            ),
+            (
+                vec![(0, vec![(1, 2)]), (1, vec![(9, 2)]), (1, vec![(12, 2)])],
+                PositionalInfoType::C,
+            ),
+            (
+                vec![(0, vec![(1, 2), (5, 2)]), (0, vec![(9, 2)])],
+                PositionalInfoType::D,
+            ),
+            (
+                vec![
+                    (0, vec![(1, 2), (5, 2)]),
+                    (1, vec![(9, 2)]),
+                    (1, vec![(12, 2)]),
+                ],
+                PositionalInfoType::D,
+            ),
+        ];
+        for (occurrences, case) in cases {
+            let occurrences = occurrences
+                .into_iter()
+                .map(|(group, spans)| {
+                    let spans = spans.into_iter().map(|(s, l)| Span::new(s, l)).collect();
+                    Occurrence::new(group, spans)
+                })
+                .collect::<Vec<_>>();
+            let info = PositionalInfo::from_occurrences(occurrences.clone());
+            assert_eq!(info.case, case);
+            let line = format!("{}{}|", base, info);
+            match parse_record(&line).unwrap() {
+                Output::MMI(record) => assert_eq!(record.positional_info, info, "{}", line),
+                other => panic!("expected an MMI record, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_lenient_identifiers() {
+        let s1 = "|MMI|170.37|Effect|C12805|[qlco]|[\"effects\"-ti-1-\"Effects\"-noun-0]|TI|21/7|";
+        let err = parse_record_with(s1, Strictness::Strict).unwrap_err();
+        assert_eq!(err.field(), Some("id"));
+        assert_eq!(err.column(), Some(0));
+        assert_eq!(
+            parse_record(s1).unwrap(),
+            parse_record_with(s1, Strictness::Lenient).unwrap().0
+        );
+
+        let (output, warnings) = parse_record_with(s1, Strictness::Lenient).unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0], err);
+        assert_eq!(warnings[1].field(), Some("cui"));
+        assert_eq!(&s1[warnings[1].column().unwrap()..][..6], "C12805");
+        match output {
+            Output::MMI(mmi) => {
+                assert!(!mmi.id.is_valid());
+                assert_eq!(mmi.cui.as_str(), "C12805");
+                assert!(!mmi.cui.is_valid());
+            }
+            _ => panic!(),
+        }
+
+        let s1 = "24119710|MMI|170.37|Effect|C1280500|[qlco]|[\"effects\"-ti-1-\"Effects\"-noun-0]|TI|21/7|";
+        let (_, warnings) = parse_record_with(s1, Strictness::Lenient).unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_mesh_branches() {
+        let s1 = "24119710|MMI|637.30|Isopoda|C0598806|[euka]|[\"Isopod\"-ab-1-\"isopod\"-adj-0]|AB|136/7|B01.050.500;A01.100;B01.050";
+        let mmi = match parse_record(s1).unwrap() {
+            Output::MMI(mmi) => mmi,
+            _ => panic!(),
+        };
+        let branches = mmi.mesh_branches().collect::<Vec<_>>();
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[0].as_str(), "B01");
+        assert_eq!(branches[1].as_str(), "A01");
+
+        let s1 = "24119710|MMI|637.30|Isopoda|C0598806|[euka]|[\"Isopod\"-ab-1-\"isopod\"-adj-0]|AB|136/7|B01.050;B01.x";
+        let err = parse_record_with(s1, Strictness::Strict).unwrap_err();
+        assert_eq!(err.field(), Some("tree_codes"));
+        assert_eq!(err.value(), Some("B01.x"));
+        assert_eq!(&s1[err.column().unwrap()..], "B01.x");
+        let (output, warnings) = parse_record_with(s1, Strictness::Lenient).unwrap();
+        assert_eq!(warnings, [err]);
+        match output {
+            Output::MMI(mmi) => {
+                let codes = mmi.tree_codes.unwrap();
+                assert_eq!(codes[0].as_str(), "B01.050");
+                assert_eq!(codes[1].as_str(), "B01.x");
+                assert!(!codes[1].is_valid());
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_trigger_spans() {
+        let expected = [
+            vec![vec![(228, 6)], vec![(136, 7)]],
+            vec![vec![(125, 9)]],
+            vec![vec![(21, 7)]],
+            vec![vec![(322, 7)]],
+            vec![vec![(7059, 5), (7073, 5)]],
+            vec![vec![(1351, 8)], vec![(1437, 8)]],
+            vec![vec![(4061, 10), (4075, 11)], vec![(4166, 10), (4180, 11)]],
+        ];
+        let contents = std::fs::read_to_string("data/MMI_sample.txt").unwrap();
+        for (line, expected) in contents.lines().zip(expected) {
+            let mmi = match parse_record(line).unwrap() {
+                Output::MMI(x) => x,
+                _ => panic!("stuff"),
+            };
+            // every sample has one trigger per occurrence or a single trigger
+            let flat = mmi
+                .trigger_spans()
+                .into_iter()
+                .flatten()
+                .map(|o| o.spans.iter().map(|s| (s.start, s.length)).collect())
+                .collect::<Vec<Vec<_>>>();
+            assert_eq!(flat, expected);
+        }
+    }
+
+    #[test]
+    fn test_occurrence_locations() {
+        let contents = std::fs::read_to_string("data/MMI_sample.txt").unwrap();
+        let line = contents.lines().next().unwrap();
+        let mmi = match parse_record(line).unwrap() {
+            Output::MMI(x) => x,
+            _ => panic!("expected an MMI record"),
+        };
+        // the groups follow the triggers ("Isopod"-ab first), not the location (TI;AB)
+        assert_eq!(mmi.location.to_string(), "TI;AB");
+        let spans = mmi.trigger_spans();
+        assert_eq!(mmi.triggers[0].text, "isopod");
+        assert_eq!(spans[0][0].spans, [Span::new(228, 6)]);
+        assert_eq!(spans[1][0].spans, [Span::new(136, 7)]);
+        let locations = mmi
+            .occurrence_locations()
+            .into_iter()
+            .map(|loc| loc.unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(locations, ["AB", "TI"]);
+
+        let line = "1|MMI|1.00|Drug|C0013227|[phsu]|[\"drug\"-ti-1-\"drug\"-noun-0,\"drugs\"-ab-1-\"drugs\"-noun-0]|TI;AB|[0/4],[10/4];[50/5]|";
+        let mmi = match parse_record(line).unwrap() {
+            Output::MMI(x) => x,
+            _ => panic!("expected an MMI record"),
+        };
+        let locations = mmi
+            .occurrence_locations()
+            .into_iter()
+            .map(|loc| loc.unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(locations, ["TI", "TI", "AB"]);
+    }
+
+    #[test]
+    fn test_assign_occurrences() {
+        let occurrence = |len: i32| Occurrence::new(0, vec![Span::new(0, len)]);
+        let two_words = Occurrence::new(0, vec![Span::new(0, 5), Span::new(10, 5)]);
+        // uneven counts use the text lengths
+        assert_eq!(
+            assign_occurrences(
+                &["drug", "medicine"],
+                &[occurrence(4), occurrence(4), occurrence(8)]
+            ),
+            vec![0, 0, 1]
+        );
+        assert_eq!(
+            assign_occurrences(
+                &["basal cells", "drug"],
+                &[two_words, occurrence(4), occurrence(4)]
+            ),
+            vec![0, 1, 1]
+        );
+        // same length triggers each get at least one occurrence
+        assert_eq!(
+            assign_occurrences(
+                &["drug", "Drug"],
+                &[occurrence(4), occurrence(4), occurrence(4)]
+            ),
+            vec![0, 0, 1]
+        );
+        // nothing fits the last trigger, so it gets nothing
+        assert_eq!(
+            assign_occurrences(
+                &["drug", "x"],
+                &[occurrence(4), occurrence(4), occurrence(4)]
+            ),
+            vec![0, 0, 0]
+        );
+        assert_eq!(
+            assign_occurrences(&[], &[occurrence(4)]),
+            Vec::<usize>::new()
+        );
+    }
 }
@@ -0,0 +1,220 @@
+//! Parser for the fielded (`--outputformat=mmi`) output of MetaMap Lite.
+//!
+//! MetaMap Lite writes the same pipe-separated columns as MetaMap, but fills
+//! some of them differently. Records are mapped onto
+//! [`MmiOutput`](crate::MmiOutput) with these gaps:
+//!
+//! * the score is MetaMap Lite's own ranking, which is not comparable to
+//!   MetaMap's MMI scores;
+//! * triggers may have only 4 or 5 parts, as the part of speech and negation
+//!   flag are left out when MetaMap Lite did not determine them. They are read
+//!   as an empty part of speech and not negated;
+//! * the tree codes column is often missing altogether (9 columns), which is
+//!   read as no tree codes;
+//! * an empty location is filled in from the locations of the triggers (a
+//!   record with neither is an error);
+//! * positions may be written as `start:length` rather than `start/length`.
+//!
+//! AA/UA records are parsed exactly like MetaMap's.
+
+use std::str::FromStr;
+
+use crate::borrowed::{MmiOutputRef, TriggerRef};
+use crate::{
+    offset_in, parse_bool, split_text, split_with_quote_context, unquote, Dialect, Location,
+    Output, ParseError, Result, Strictness, Warnings, MMI_FIELDS,
+};
+
+/// Parses a single line of MetaMap Lite's fielded output, see the
+/// [module documentation](self) for how it differs from [`crate::parse_record`].
+///
+/// ```rust
+/// let line = "16125203|MMI|3.58|Myocardial Infarction|C0027051|[dsyn]|[\"heart attack\"-tx-1-\"heart attack\"]|TX|0:12";
+/// let record = mmi_parser::lite::parse_record(line).unwrap();
+/// assert_eq!(record.to_string(), "16125203|MMI|3.58|Myocardial Infarction|C0027051|[dsyn]|[\"heart attack\"-tx-1-\"heart attack\"--0]|TX|0/12|");
+/// ```
+pub fn parse_record(text: &str) -> Result<Output> {
+    parse_record_with(text, Strictness::Lenient).map(|(output, _)| output)
+}
+
+/// Like [`parse_record`], with a choice of how strictly identifiers are
+/// validated (see [`crate::parse_record_with`]).
+pub fn parse_record_with(text: &str, strictness: Strictness) -> Result<(Output, Vec<ParseError>)> {
+    let parts = split_text(text);
+    if !parts.get(1).is_some_and(|p| p.eq_ignore_ascii_case("MMI")) {
+        return crate::parse_record_with(text, strictness);
+    }
+    if parts.len() != MMI_FIELDS.len() && parts.len() != MMI_FIELDS.len() - 1 {
+        return Err(ParseError::FieldCount {
+            expected: MMI_FIELDS.len(),
+            found: parts.len(),
+        });
+    }
+    // the tree codes column may be missing altogether
+    let mut fields = [""; 10];
+    fields[..parts.len()].copy_from_slice(&parts);
+    let mut warnings = Warnings::new(strictness);
+    let output = MmiOutputRef::from_fields(fields, Dialect::Lite, &mut warnings)?.into_owned();
+    Ok((Output::MMI(output), warnings.found))
+}
+
+/// Parses the triggers of a record, each of 4 to 6 `-`-separated parts.
+///
+/// `column` is the byte column at which `info` starts within the record
+/// and is used to locate errors.
+pub(crate) fn parse_triggers(info: &str, column: usize) -> Result<Vec<TriggerRef<'_>>> {
+    split_with_quote_context(info, ',')
+        .into_iter()
+        .map(|t| parse_trigger(t, column + offset_in(info, t)))
+        .collect()
+}
+
+/// Parses a trigger of 4 to 6 `-`-separated parts.
+fn parse_trigger(trigger: &str, column: usize) -> Result<TriggerRef<'_>> {
+    let clean = trigger.trim_start_matches('[').trim_end_matches(']');
+    let parts = split_with_quote_context(clean, '-');
+    let col = |part: &str| column + offset_in(trigger, part);
+    if !(4..=6).contains(&parts.len()) {
+        return Err(ParseError::InvalidValue {
+            field: "triggers",
+            value: trigger.to_string(),
+            column,
+        });
+    }
+    let loc = Location::from_str(parts[1]).map_err(|_| ParseError::UnknownLocation {
+        field: "triggers",
+        value: parts[1].to_string(),
+        column: col(parts[1]),
+    })?;
+    let loc_position = parts[2]
+        .parse::<i32>()
+        .map_err(|_| ParseError::InvalidInt {
+            field: "triggers",
+            value: parts[2].to_string(),
+            column: col(parts[2]),
+        })?;
+    let negation = match parts.get(5).copied() {
+        None | Some("") => false,
+        Some("true") => true,
+        Some("false") => false,
+        Some(flag) => parse_bool(flag).map_err(|_| ParseError::InvalidValue {
+            field: "triggers",
+            value: flag.to_string(),
+            column: col(flag),
+        })?,
+    };
+    Ok(TriggerRef {
+        name: unquote(parts[0]),
+        loc,
+        loc_position,
+        text: unquote(parts[3]),
+        part_of_speech: parts.get(4).map(|p| unquote(p)).unwrap_or_default(),
+        negation,
+    })
+}
+
+/// The location of a record whose location field was left empty, made of the
+/// locations of its triggers. Errors when there are none to take it from.
+pub(crate) fn trigger_location(triggers: &[TriggerRef], column: usize) -> Result<Location> {
+    let location = Location::new(
+        triggers
+            .iter()
+            .flat_map(|t| t.loc.sections().iter().cloned()),
+    );
+    if location.sections().is_empty() {
+        return Err(ParseError::UnknownLocation {
+            field: "location",
+            value: String::new(),
+            column,
+        });
+    }
+    Ok(location)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MmiOutput, PositionalInfoType};
+
+    fn mmi(line: &str) -> MmiOutput {
+        match parse_record(line).unwrap() {
+            Output::MMI(mmi) => mmi,
+            other => panic!("expected an MMI record, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lite_record() {
+        let record = mmi("16125203|MMI|3.58|Myocardial Infarction|C0027051|[dsyn]|[\"heart attack\"-tx-1-\"heart attack\",\"MI\"-tx-2-\"MI\"-noun-true]||0:12,40:2");
+        assert_eq!(record.score, 3.58);
+        assert_eq!(record.triggers.len(), 2);
+        assert_eq!(record.triggers[0].part_of_speech, "");
+        assert!(!record.triggers[0].negation);
+        assert!(record.triggers[1].negation);
+        assert_eq!(record.location.to_string(), "TX");
+        assert_eq!(record.positional_info.case, PositionalInfoType::B);
+        assert_eq!(record.positional_info.to_string(), "0/12,40/2");
+        assert_eq!(record.tree_codes, None);
+    }
+
+    #[test]
+    fn test_lite_accepts_metamap_records() {
+        let line = "24119710|MMI|5.18|Carcinoma of lung|C0684249|[neop]|[\"LUNG CANCER\"-tx-1-\"lung cancer\"-noun-0]|TX|0/11|C04.588.894.797.520;C08.381.540";
+        assert_eq!(
+            parse_record(line).unwrap(),
+            crate::parse_record(line).unwrap()
+        );
+        let aa = "1|AA|FY|fiscal years|1|2|3|12|9362:2";
+        assert_eq!(parse_record(aa).unwrap(), crate::parse_record(aa).unwrap());
+    }
+
+    #[test]
+    fn test_lite_errors() {
+        let line = "1|MMI|3.58|Name|C0027051|[dsyn]|[\"a\"-tx-1]|TX|0:1";
+        assert!(matches!(
+            parse_record(line),
+            Err(ParseError::InvalidValue {
+                field: "triggers",
+                column: 32,
+                ..
+            })
+        ));
+        let line = "1|MMI|3.58|Name|C0027051|[dsyn]|[\"a\"-tx-1-\"a\"-noun-maybe]|TX|0:1";
+        assert!(matches!(
+            parse_record(line),
+            Err(ParseError::InvalidValue {
+                field: "triggers",
+                column: 51,
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse_record("1|MMI|3.58|Name|C0027051"),
+            Err(ParseError::FieldCount { found: 5, .. })
+        ));
+        let line = "1|MMI|3.58|Name|C002|[dsyn]|[\"a\"-tx-1-\"a\"]|TX|0:1";
+        assert!(parse_record_with(line, Strictness::Strict).is_err());
+        let (_, warnings) = parse_record_with(line, Strictness::Lenient).unwrap();
+        assert_eq!(warnings[0].field(), Some("cui"));
+        // without triggers, an empty location cannot be filled in
+        let line = "1|MMI|3.58|Name|C0027051|[dsyn]|||0:1";
+        assert!(matches!(
+            parse_record(line),
+            Err(ParseError::UnknownLocation {
+                field: "location",
+                column: 33,
+                ..
+            })
+        ));
+        assert!(parse_record("1|MMI|3.58|Name|C0027051|[dsyn]|[]||0:1").is_err());
+        let line = "1|MMI|3.58|Name|C0027051|[dsyn]|[\"a\"-tx-1-\"a\"]|TX|0:1|C14;x1";
+        assert!(parse_record_with(line, Strictness::Strict).is_err());
+        let (output, warnings) = parse_record_with(line, Strictness::Lenient).unwrap();
+        assert_eq!(warnings[0].field(), Some("tree_codes"));
+        assert_eq!(&line[warnings[0].column().unwrap()..], "x1");
+        match output {
+            Output::MMI(mmi) => assert_eq!(mmi.tree_codes.unwrap().len(), 2),
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,1396 @@
+//! Parser for MetaMap's Prolog machine output (`--machine_output` / `-q`).
+//!
+//! Machine output is a sequence of Prolog terms, each ending with a `.`:
+//!
+//! ```text
+//! utterance('24119710.ti.1',"Effects of heart attack",0/23,[]).
+//! phrase('heart attack',[head([lexmatch(['heart attack']),inputmatch([heart,attack]),tag(noun),tokens([heart,attack])])],11/12,[]).
+//! candidates(1,0,0,1,[ev(-1000,'C0027051','Heart attack','Myocardial Infarction',[heart,attack],[dsyn],[[[1,2],[1,2],0]],yes,no,['MSH'],[11/12],0,0)]).
+//! mappings([map(-1000,[ev(-1000,'C0027051','Heart attack','Myocardial Infarction',[heart,attack],[dsyn],[[[1,2],[1,2],0]],yes,no,['MSH'],[11/12],0,0)])]).
+//! 'EOU'.
+//! ```
+//!
+//! [`parse_term`] reads a single generic [`Term`], and [`MachineReader`] turns a
+//! whole stream into typed [`Utterance`]s. [`to_mmi_outputs`] projects the
+//! utterances of a document onto [`MmiOutput`] records. [`MmoReader`] groups
+//! them into documents along with their abbreviations (`aas` terms), which
+//! [`Mmo::to_outputs`] projects onto MMI and AA records.
+//!
+//! The typed structures are also used for MetaMap's other structured formats
+//! (see [`xml`](crate::xml)), which describe the same output: [`Mmo`] holds
+//! everything produced for one document.
+
+use serde::{Deserialize, Serialize};
+use std::error;
+use std::fmt::{self, Display};
+use std::io::{self, BufRead, Read};
+use std::str::FromStr;
+
+use crate::aggregate::{aggregate, ConceptMatch};
+use crate::{AaOutput, AaPosInfo, AbbreviationType, DocumentId, MmiOutput, Output, Section, Span};
+
+/// A Prolog term as written by MetaMap.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    /// An atom, quoted (`'EOU'`) or not (`yes`)
+    Atom(String),
+    /// A double quoted string
+    Str(String),
+    Int(i64),
+    Float(f64),
+    /// A compound term such as `tag(noun)`. The infix operators `/`, `*`, `:` and `-`
+    /// (as in `0/12`) are compounds with the operator as their name.
+    Compound {
+        name: String,
+        args: Vec<Term>,
+    },
+    List(Vec<Term>),
+}
+
+impl Term {
+    /// Name of an atom or compound term.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Term::Atom(name) | Term::Compound { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Arguments of a compound term, empty for anything else.
+    pub fn args(&self) -> &[Term] {
+        match self {
+            Term::Compound { args, .. } => args,
+            _ => &[],
+        }
+    }
+
+    /// Text of an atom or string.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Term::Atom(s) | Term::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Term::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Term]> {
+        match self {
+            Term::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// An integer that fits the `i32` fields of the parsed types.
+    fn as_i32(&self) -> Option<i32> {
+        self.as_int().and_then(|i| i32::try_from(i).ok())
+    }
+
+    /// The two sides of a `start/length` position.
+    fn as_position(&self) -> Option<(i32, i32)> {
+        match self {
+            Term::Compound { name, args } if name == "/" && args.len() == 2 => {
+                Some((args[0].as_i32()?, args[1].as_i32()?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Text of an atom, string or number, the way MetaMap would have written it.
+    fn to_text(&self) -> Option<String> {
+        match self {
+            Term::Atom(s) | Term::Str(s) => Some(s.clone()),
+            Term::Int(i) => Some(i.to_string()),
+            Term::Float(x) => Some(x.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// A term that could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// byte offset within the text where reading failed
+    pub offset: usize,
+    pub message: String,
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl error::Error for SyntaxError {}
+
+/// How deeply lists, compounds, parentheses and operators may be nested in a
+/// term, so that malformed input cannot exhaust the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Parses a single term followed by a terminating `.`.
+///
+/// Terms nested more than 128 levels deep are rejected.
+///
+/// ```rust
+/// use mmi_parser::machine::{parse_term, Term};
+///
+/// let term = parse_term("tag(noun).").unwrap();
+/// assert_eq!(term.name(), Some("tag"));
+/// assert_eq!(term.args(), [Term::Atom("noun".to_string())]);
+/// ```
+pub fn parse_term(text: &str) -> Result<Term, SyntaxError> {
+    let mut parser = TermParser {
+        text,
+        pos: 0,
+        depth: 0,
+    };
+    let term = parser.expression()?;
+    parser.skip_whitespace();
+    parser.expect('.')?;
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        return Err(parser.error("unexpected text after the end of the term"));
+    }
+    Ok(term)
+}
+
+struct TermParser<'a> {
+    text: &'a str,
+    pos: usize,
+    /// number of lists, compounds and parentheses the parser is inside of
+    depth: usize,
+}
+
+impl TermParser<'_> {
+    fn error(&self, message: &str) -> SyntaxError {
+        SyntaxError {
+            offset: self.pos,
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.text[self.pos..].chars().nth(1)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    /// Runs `parse` one nesting level deeper, failing past [`MAX_DEPTH`].
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, SyntaxError>,
+    ) -> Result<T, SyntaxError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("terms are nested too deeply"));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SyntaxError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            Some(_) => Err(self.error(&format!("expected `{}`", expected))),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    /// Skips whitespace and `%` line comments.
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.bump();
+            } else if c == '%' {
+                while !matches!(self.bump(), Some('\n') | None) {}
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn expression(&mut self) -> Result<Term, SyntaxError> {
+        let mut left = self.primary()?;
+        // each operator nests the terms before it one level deeper
+        let mut operators = 0;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(op @ ('/' | '*' | ':' | '-')) => {
+                    operators += 1;
+                    if self.depth + operators > MAX_DEPTH {
+                        return Err(self.error("terms are nested too deeply"));
+                    }
+                    self.bump();
+                    let right = self.primary()?;
+                    left = Term::Compound {
+                        name: op.to_string(),
+                        args: vec![left, right],
+                    };
+                }
+                _ => return Ok(left),
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Term, SyntaxError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Err(self.error("unexpected end of input")),
+            Some('[') => {
+                self.bump();
+                self.nested(|parser| parser.sequence(']')).map(Term::List)
+            }
+            Some('(') => {
+                self.bump();
+                self.nested(|parser| {
+                    let term = parser.expression()?;
+                    parser.skip_whitespace();
+                    parser.expect(')')?;
+                    Ok(term)
+                })
+            }
+            Some('"') => Ok(Term::Str(self.quoted('"')?)),
+            Some('\'') => {
+                let name = self.quoted('\'')?;
+                self.compound_or_atom(name)
+            }
+            Some(c) if c.is_ascii_digit() => self.number(),
+            Some('-') if matches!(self.peek_second(), Some(c) if c.is_ascii_digit()) => {
+                self.number()
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_alphanumeric() || c == '_') {
+                    self.bump();
+                }
+                let name = self.text[start..self.pos].to_string();
+                self.compound_or_atom(name)
+            }
+            Some(c) => Err(self.error(&format!("unexpected character `{}`", c))),
+        }
+    }
+
+    /// Comma separated terms up to and including `close`.
+    fn sequence(&mut self, close: char) -> Result<Vec<Term>, SyntaxError> {
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(close) {
+            self.bump();
+            return Ok(items);
+        }
+        loop {
+            items.push(self.expression()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some(c) if c == close => {
+                    self.bump();
+                    return Ok(items);
+                }
+                Some(_) => return Err(self.error(&format!("expected `,` or `{}`", close))),
+                None => return Err(self.error("unexpected end of input")),
+            }
+        }
+    }
+
+    fn compound_or_atom(&mut self, name: String) -> Result<Term, SyntaxError> {
+        if self.peek() == Some('(') {
+            self.bump();
+            let args = self.nested(|parser| parser.sequence(')'))?;
+            Ok(Term::Compound { name, args })
+        } else {
+            Ok(Term::Atom(name))
+        }
+    }
+
+    /// A quoted atom or string. The quote is escaped by doubling it or with a backslash.
+    fn quoted(&mut self, quote: char) -> Result<String, SyntaxError> {
+        self.bump();
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error("unexpected end of input")),
+                Some(c) if c == quote => {
+                    if self.peek() == Some(quote) {
+                        self.bump();
+                        out.push(quote);
+                    } else {
+                        return Ok(out);
+                    }
+                }
+                Some('\\') => match self.bump() {
+                    None => return Err(self.error("unexpected end of input")),
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some(c) => out.push(c),
+                },
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Term, SyntaxError> {
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.bump();
+        }
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            self.bump();
+        }
+        let is_float =
+            self.peek() == Some('.') && matches!(self.peek_second(), Some(c) if c.is_ascii_digit());
+        if is_float {
+            self.bump();
+            while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+                self.bump();
+            }
+        }
+        let digits = &self.text[start..self.pos];
+        let term = if is_float {
+            digits.parse().map(Term::Float).ok()
+        } else {
+            digits.parse().map(Term::Int).ok()
+        };
+        term.ok_or_else(|| SyntaxError {
+            offset: start,
+            message: format!("invalid number `{}`", digits),
+        })
+    }
+}
+
+/// One unit of a phrase's syntactic analysis, e.g. `head([...])`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyntaxUnit {
+    /// `head`, `mod`, `verb`, `prep`, `punc`, ...
+    pub kind: String,
+    /// the words of the input text the unit covers
+    pub input_match: Vec<String>,
+    /// part of speech, if MetaMap tagged the unit
+    pub tag: Option<String>,
+}
+
+/// A concept MetaMap considered for a phrase (an `ev` term).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candidate {
+    /// negated score, -1000 being a perfect match
+    pub score: i32,
+    pub cui: String,
+    /// the concept string that matched
+    pub matched: String,
+    pub preferred_name: String,
+    /// the words of the text that matched, lowercased
+    pub matched_words: Vec<String>,
+    /// semantic type abbreviations
+    pub semantic_types: Vec<String>,
+    /// whether the match involves the head of the phrase
+    pub head: bool,
+    /// whether the match covers more than the phrase
+    pub overmatch: bool,
+    /// vocabularies the concept string comes from
+    pub sources: Vec<String>,
+    /// where the matched text is
+    pub positions: Vec<Span>,
+    /// 0 for a normal match, otherwise MetaMap's candidate status (not in older versions)
+    pub status: Option<i32>,
+    /// whether MetaMap considered the concept negated (not in older versions)
+    pub negated: bool,
+}
+
+/// One of the best combinations of candidates covering a phrase.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mapping {
+    /// negated score, -1000 being a perfect mapping
+    pub score: i32,
+    pub candidates: Vec<Candidate>,
+}
+
+/// A phrase of an utterance along with what MetaMap found for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Phrase {
+    pub text: String,
+    pub syntax: Vec<SyntaxUnit>,
+    /// start of the phrase in the document
+    pub start: i32,
+    pub length: i32,
+    pub candidates: Vec<Candidate>,
+    pub mappings: Vec<Mapping>,
+}
+
+/// A sentence (or title) of a document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Utterance {
+    /// `<document id>.<section>.<number>`, e.g. `24119710.ti.1`
+    pub id: String,
+    pub text: String,
+    /// start of the utterance in the document
+    pub start: i32,
+    pub length: i32,
+    pub phrases: Vec<Phrase>,
+}
+
+/// Splits an utterance id into document id, section and number, if it has that form.
+pub(crate) fn split_utterance_id(id: &str) -> Option<(&str, &str, i32)> {
+    let mut parts = id.rsplitn(3, '.');
+    let number = parts.next()?.parse().ok()?;
+    let section = parts.next()?;
+    let document = parts.next()?;
+    Some((document, section, number))
+}
+
+impl Utterance {
+    fn id_parts(&self) -> Option<(&str, &str, i32)> {
+        split_utterance_id(&self.id)
+    }
+
+    /// Id of the document the utterance belongs to (the whole id if it has no section).
+    pub fn document_id(&self) -> &str {
+        self.id_parts().map_or(&self.id, |(document, ..)| document)
+    }
+
+    /// Section the utterance was found in, e.g. `TI`.
+    pub fn section(&self) -> Option<Section> {
+        self.id_parts()
+            .and_then(|(_, section, _)| Section::from_str(section).ok())
+    }
+
+    /// Number of the utterance within its section (starting with 1).
+    pub fn number(&self) -> Option<i32> {
+        self.id_parts().map(|(.., number)| number)
+    }
+}
+
+/// An acronym or abbreviation MetaMap found in a document, along with its
+/// expansion.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Abbreviation {
+    pub short_form: String,
+    pub long_form: String,
+    /// number of tokens (including whitespace) in the short form
+    pub short_token_count: i32,
+    /// number of characters in the short form
+    pub short_length: i32,
+    /// number of tokens (including whitespace) in the long form
+    pub long_token_count: i32,
+    /// number of characters in the long form
+    pub long_length: i32,
+    /// start of the first occurrence of the short form in the document
+    pub start: i32,
+}
+
+/// Everything MetaMap produced for one document (a MetaMap Output, or MMO).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mmo {
+    /// the command MetaMap was run with, if the format records it
+    pub command_line: Option<String>,
+    pub abbreviations: Vec<Abbreviation>,
+    pub utterances: Vec<Utterance>,
+}
+
+impl Mmo {
+    /// Id of the document, taken from its first utterance.
+    pub fn document_id(&self) -> Option<&str> {
+        self.utterances.first().map(Utterance::document_id)
+    }
+
+    /// Projects the document onto fielded MMI records: the concepts as
+    /// [`to_mmi_outputs`] builds them, followed by one `AA` record per
+    /// abbreviation.
+    pub fn to_outputs(&self) -> Vec<Output> {
+        let id = self.document_id().unwrap_or_default();
+        let mut outputs = to_mmi_outputs(&self.utterances)
+            .into_iter()
+            .map(Output::MMI)
+            .collect::<Vec<_>>();
+        outputs.extend(self.abbreviations.iter().map(|aa| {
+            Output::AA(AaOutput {
+                id: DocumentId::new_unchecked(id.to_string()),
+                abbreviation_type: AbbreviationType::AA,
+                short_form: aa.short_form.clone(),
+                long_form: aa.long_form.clone(),
+                short_token_count: aa.short_token_count,
+                short_character_count: aa.short_length,
+                long_token_count: aa.long_token_count,
+                long_character_count: aa.long_length,
+                positional_info: AaPosInfo::new(aa.start, aa.short_length),
+            })
+        }));
+        outputs
+    }
+}
+
+/// Errors produced while reading machine output with a [`MachineReader`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MachineError {
+    /// The underlying reader failed. No further items are produced after this.
+    Io(io::Error),
+    /// A term could not be read. Reading continues with the next line starting
+    /// a term.
+    Syntax {
+        line_number: usize,
+        error: SyntaxError,
+    },
+    /// A term was readable but did not have the expected shape.
+    Structure { line_number: usize, message: String },
+}
+
+impl MachineError {
+    /// 1-based line number of the term the error occurred in, if any.
+    pub fn line_number(&self) -> Option<usize> {
+        match self {
+            MachineError::Io(_) => None,
+            MachineError::Syntax { line_number, .. }
+            | MachineError::Structure { line_number, .. } => Some(*line_number),
+        }
+    }
+}
+
+impl Display for MachineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MachineError::Io(e) => write!(f, "could not read input: {}", e),
+            MachineError::Syntax { line_number, error } => {
+                write!(f, "line {}: {}", line_number, error)
+            }
+            MachineError::Structure {
+                line_number,
+                message,
+            } => write!(f, "line {}: {}", line_number, message),
+        }
+    }
+}
+
+impl error::Error for MachineError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            MachineError::Io(e) => Some(e),
+            MachineError::Syntax { error, .. } => Some(error),
+            MachineError::Structure { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for MachineError {
+    fn from(e: io::Error) -> Self {
+        MachineError::Io(e)
+    }
+}
+
+/// Whether `text` (the start of an input) looks like machine output rather than
+/// fielded MMI.
+pub fn looks_like_machine_output(text: &str) -> bool {
+    let start = text.trim_start();
+    ["args(", "aas(", "neg_list(", "utterance("]
+        .iter()
+        .any(|term| start.starts_with(term))
+}
+
+/// Longest term, in bytes, a [`MachineReader`] reads before giving up on it.
+const MAX_TERM_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Most lines a term read by a [`MachineReader`] may span.
+const MAX_TERM_LINES: usize = 100_000;
+
+/// Top-level terms of machine output, each starting a line.
+const TERM_STARTS: [&str; 8] = [
+    "args(",
+    "aas(",
+    "neg_list(",
+    "utterance(",
+    "phrase(",
+    "candidates(",
+    "mappings(",
+    "'EOU'",
+];
+
+/// Whether `line` starts one of the [top-level terms](TERM_STARTS).
+fn starts_term(line: &str) -> bool {
+    TERM_STARTS.iter().any(|term| line.starts_with(term))
+}
+
+/// Brackets and quotes left open by the lines of a term read so far.
+#[derive(Debug, Default)]
+struct Nesting {
+    depth: i64,
+    quote: Option<char>,
+}
+
+impl Nesting {
+    fn scan(&mut self, line: &str) {
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match (self.quote, c) {
+                (Some(_), '\\') => {
+                    chars.next();
+                }
+                (Some(quote), c) if c == quote => self.quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"') => self.quote = Some(c),
+                (None, '(' | '[') => self.depth += 1,
+                (None, ')' | ']') => self.depth -= 1,
+                (None, '%') => break,
+                _ => {}
+            }
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.depth <= 0 && self.quote.is_none()
+    }
+}
+
+/// Iterator over the [`Utterance`]s of machine output read from any [`BufRead`].
+///
+/// Each utterance is yielded once its `'EOU'` marker is read. The command line of
+/// `args` terms and the abbreviations of `aas` terms are kept for [`MmoReader`],
+/// while `neg_list` terms and anything unknown are skipped. Terms longer than
+/// 16 MiB or 100,000 lines are reported as syntax errors rather than read whole.
+pub struct MachineReader<R> {
+    reader: R,
+    buffer: String,
+    line_number: usize,
+    current: Option<Utterance>,
+    /// a line that was read while looking for the end of the previous term
+    pending: Option<String>,
+    /// whether lines are skipped up to the next term, after one failed
+    resync: bool,
+    /// command line of the last `args` term, until a document takes it
+    command_line: Option<String>,
+    /// abbreviations of the last `aas` term, until a document takes them
+    abbreviations: Vec<Abbreviation>,
+    finished: bool,
+}
+
+impl<R: BufRead> MachineReader<R> {
+    /// Creates a new reader over `reader`.
+    pub fn new(reader: R) -> Self {
+        MachineReader {
+            reader,
+            buffer: String::new(),
+            line_number: 0,
+            current: None,
+            pending: None,
+            resync: false,
+            command_line: None,
+            abbreviations: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Reads the next line, at most `limit` bytes of it. The rest of a longer
+    /// line is discarded.
+    fn read_line(&mut self, limit: usize) -> io::Result<Option<String>> {
+        let mut bytes = Vec::new();
+        let read = (&mut self.reader)
+            .take(limit as u64)
+            .read_until(b'\n', &mut bytes)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if read == limit && bytes.last() != Some(&b'\n') {
+            loop {
+                let available = self.reader.fill_buf()?;
+                if available.is_empty() {
+                    break;
+                }
+                match available.iter().position(|&b| b == b'\n') {
+                    Some(end) => {
+                        self.reader.consume(end + 1);
+                        break;
+                    }
+                    None => {
+                        let length = available.len();
+                        self.reader.consume(length);
+                    }
+                }
+            }
+        }
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    fn syntax_error(&self, line_number: usize, message: &str) -> MachineError {
+        MachineError::Syntax {
+            line_number,
+            error: SyntaxError {
+                offset: self.buffer.len(),
+                message: message.to_string(),
+            },
+        }
+    }
+
+    /// Reads the next complete term along with the line it starts on.
+    ///
+    /// The lines of a term are only parsed once its brackets and quotes are
+    /// closed and a line ends with `.`. After a term that could not be read,
+    /// lines are skipped up to the next one starting a known term.
+    fn next_term(&mut self) -> Option<Result<(usize, Term), MachineError>> {
+        self.buffer.clear();
+        let mut nesting = Nesting::default();
+        let mut start_line = 0;
+        let mut lines = 0;
+        loop {
+            let line = match self.pending.take() {
+                Some(line) => line,
+                None => match self.read_line(MAX_TERM_LENGTH + 1 - self.buffer.len()) {
+                    Ok(Some(line)) => {
+                        self.line_number += 1;
+                        line
+                    }
+                    Ok(None) if self.buffer.trim().is_empty() => return None,
+                    Ok(None) => {
+                        return Some(Err(self.syntax_error(start_line, "unexpected end of input")))
+                    }
+                    Err(e) => {
+                        self.finished = true;
+                        return Some(Err(MachineError::Io(e)));
+                    }
+                },
+            };
+            if self.buffer.trim().is_empty() {
+                if line.trim().is_empty() || (self.resync && !starts_term(&line)) {
+                    continue;
+                }
+                self.resync = false;
+                start_line = self.line_number;
+            } else if nesting.quote.is_none() && starts_term(&line) {
+                // the term being read never ended, and the next one starts here
+                self.pending = Some(line);
+                return Some(Err(self.syntax_error(start_line, "unexpected end of term")));
+            }
+            self.buffer.push_str(&line);
+            lines += 1;
+            if self.buffer.len() > MAX_TERM_LENGTH || lines > MAX_TERM_LINES {
+                self.resync = true;
+                return Some(Err(self.syntax_error(start_line, "term is too long")));
+            }
+            nesting.scan(&line);
+            if !nesting.is_closed() || !line.trim_end().ends_with('.') {
+                continue;
+            }
+            return Some(match parse_term(&self.buffer) {
+                Ok(term) => Ok((start_line, term)),
+                Err(error) => {
+                    self.resync = true;
+                    Err(MachineError::Syntax {
+                        line_number: start_line,
+                        error,
+                    })
+                }
+            });
+        }
+    }
+
+    /// Adds a term to the utterance being read, returning the previous
+    /// utterance if the term starts a new one.
+    fn add_term(
+        &mut self,
+        line_number: usize,
+        term: Term,
+    ) -> Result<Option<Utterance>, MachineError> {
+        let structure = |message: &str| MachineError::Structure {
+            line_number,
+            message: format!("{} in `{}` term", message, term.name().unwrap_or_default()),
+        };
+        match (term.name(), term.args().len()) {
+            (Some("utterance"), 3..) => {
+                let args = term.args();
+                let id = args[0].to_text().ok_or_else(|| structure("invalid id"))?;
+                let text = args[1].to_text().ok_or_else(|| structure("invalid text"))?;
+                let (start, length) = args[2]
+                    .as_position()
+                    .ok_or_else(|| structure("invalid position"))?;
+                let utterance = Utterance {
+                    id,
+                    text,
+                    start,
+                    length,
+                    phrases: Vec::new(),
+                };
+                Ok(self.current.replace(utterance))
+            }
+            (Some("phrase"), 3..) => {
+                let args = term.args();
+                let text = args[0].to_text().ok_or_else(|| structure("invalid text"))?;
+                let syntax = args[1]
+                    .as_list()
+                    .ok_or_else(|| structure("invalid syntax"))?
+                    .iter()
+                    .map(syntax_unit)
+                    .collect();
+                let (start, length) = args[2]
+                    .as_position()
+                    .ok_or_else(|| structure("invalid position"))?;
+                let utterance = self
+                    .current
+                    .as_mut()
+                    .ok_or_else(|| structure("no utterance"))?;
+                utterance.phrases.push(Phrase {
+                    text,
+                    syntax,
+                    start,
+                    length,
+                    candidates: Vec::new(),
+                    mappings: Vec::new(),
+                });
+                Ok(None)
+            }
+            (Some("candidates"), 1..) => {
+                // older versions only have the list, newer ones have counts before it
+                let list = term.args().last().and_then(Term::as_list);
+                let candidates = list
+                    .ok_or_else(|| structure("invalid candidate list"))?
+                    .iter()
+                    .map(candidate)
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| structure("invalid candidate"))?;
+                self.current_phrase()
+                    .ok_or_else(|| structure("no phrase"))?
+                    .candidates = candidates;
+                Ok(None)
+            }
+            (Some("mappings"), 1) => {
+                let mappings = term.args()[0]
+                    .as_list()
+                    .ok_or_else(|| structure("invalid mapping list"))?
+                    .iter()
+                    .map(mapping)
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| structure("invalid mapping"))?;
+                self.current_phrase()
+                    .ok_or_else(|| structure("no phrase"))?
+                    .mappings = mappings;
+                Ok(None)
+            }
+            (Some("args"), 1..) => {
+                self.command_line = term.args()[0].to_text();
+                Ok(None)
+            }
+            (Some("aas"), 1) => {
+                self.abbreviations = term.args()[0]
+                    .as_list()
+                    .ok_or_else(|| structure("invalid abbreviation list"))?
+                    .iter()
+                    .map(abbreviation)
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| structure("invalid abbreviation"))?;
+                Ok(None)
+            }
+            (Some("EOU"), 0) => Ok(self.current.take()),
+            (name, _) => {
+                log::debug!("Skipping machine output term {:?}", name);
+                Ok(None)
+            }
+        }
+    }
+
+    fn current_phrase(&mut self) -> Option<&mut Phrase> {
+        self.current.as_mut()?.phrases.last_mut()
+    }
+}
+
+/// Reads a syntax unit like `head([inputmatch([heart]),tag(noun)])`.
+fn syntax_unit(term: &Term) -> SyntaxUnit {
+    let mut unit = SyntaxUnit {
+        kind: term.name().unwrap_or_default().to_string(),
+        input_match: Vec::new(),
+        tag: None,
+    };
+    let features = term
+        .args()
+        .first()
+        .and_then(Term::as_list)
+        .unwrap_or_default();
+    for feature in features {
+        match feature.name() {
+            Some("inputmatch") => {
+                unit.input_match = text_list(feature.args().first()).unwrap_or_default();
+            }
+            Some("tag") => unit.tag = feature.args().first().and_then(Term::to_text),
+            _ => {}
+        }
+    }
+    unit
+}
+
+fn text_list(term: Option<&Term>) -> Option<Vec<String>> {
+    term?.as_list()?.iter().map(Term::to_text).collect()
+}
+
+fn yes_no(term: &Term) -> Option<bool> {
+    match term.as_text()? {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Reads an `ev` term with 11 (before MetaMap 2011) or 13 arguments.
+fn candidate(term: &Term) -> Option<Candidate> {
+    let args = term.args();
+    if term.name() != Some("ev") || args.len() < 11 {
+        return None;
+    }
+    let positions = args[10]
+        .as_list()?
+        .iter()
+        .map(|p| p.as_position().map(|(s, l)| Span::new(s, l)))
+        .collect::<Option<Vec<_>>>()?;
+    let status = match args.get(11).and_then(Term::as_int) {
+        Some(status) => Some(i32::try_from(status).ok()?),
+        None => None,
+    };
+    Some(Candidate {
+        score: args[0].as_i32()?,
+        cui: args[1].to_text()?,
+        matched: args[2].to_text()?,
+        preferred_name: args[3].to_text()?,
+        matched_words: text_list(args.get(4))?,
+        semantic_types: text_list(args.get(5))?,
+        head: yes_no(&args[7])?,
+        overmatch: yes_no(&args[8])?,
+        sources: text_list(args.get(9))?,
+        positions,
+        status,
+        negated: args.get(12).and_then(Term::as_int).unwrap_or(0) == 1,
+    })
+}
+
+/// The operands of a chain of `*` operators, such as `a*b*c`, in order.
+fn star_operands(term: &Term) -> Vec<&Term> {
+    let mut operands = Vec::new();
+    let mut term = term;
+    while let Term::Compound { name, args } = term {
+        if name != "*" || args.len() != 2 {
+            break;
+        }
+        operands.push(&args[1]);
+        term = &args[0];
+    }
+    operands.push(term);
+    operands.reverse();
+    operands
+}
+
+/// Reads an abbreviation like `'HIV'*'human immunodeficiency virus'*[1,3,5,28,77]*[...]`,
+/// its counts being the token and character counts of the short and long forms
+/// followed by the start of the short form (missing before MetaMap 2013).
+fn abbreviation(term: &Term) -> Option<Abbreviation> {
+    let operands = star_operands(term);
+    if operands.len() < 3 {
+        return None;
+    }
+    let counts = operands[2]
+        .as_list()?
+        .iter()
+        .map(Term::as_i32)
+        .collect::<Option<Vec<_>>>()?;
+    if counts.len() < 4 {
+        return None;
+    }
+    Some(Abbreviation {
+        short_form: operands[0].to_text()?,
+        long_form: operands[1].to_text()?,
+        short_token_count: counts[0],
+        short_length: counts[1],
+        long_token_count: counts[2],
+        long_length: counts[3],
+        start: counts.get(4).copied().unwrap_or(0),
+    })
+}
+
+/// Reads a `map(Score, [ev(...), ...])` term.
+fn mapping(term: &Term) -> Option<Mapping> {
+    let args = term.args();
+    if term.name() != Some("map") || args.len() != 2 {
+        return None;
+    }
+    Some(Mapping {
+        score: args[0].as_i32()?,
+        candidates: args[1]
+            .as_list()?
+            .iter()
+            .map(candidate)
+            .collect::<Option<Vec<_>>>()?,
+    })
+}
+
+impl<R: BufRead> Iterator for MachineReader<R> {
+    type Item = Result<Utterance, MachineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            match self.next_term() {
+                None => {
+                    // input ended without a final 'EOU'
+                    self.finished = true;
+                    return self.current.take().map(Ok);
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok((line_number, term))) => match self.add_term(line_number, term) {
+                    Ok(Some(utterance)) => return Some(Ok(utterance)),
+                    Ok(None) => continue,
+                    Err(e) => return Some(Err(e)),
+                },
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over the documents of machine output read from any [`BufRead`], as
+/// [`Mmo`]s holding their utterances along with the command line and
+/// abbreviations MetaMap wrote before them.
+///
+/// A document ends where an utterance of another document starts (see
+/// [`Utterance::document_id`]), and errors are passed on as the
+/// [`MachineReader`] reports them.
+pub struct MmoReader<R> {
+    utterances: MachineReader<R>,
+    current: Option<Mmo>,
+}
+
+impl<R: BufRead> MmoReader<R> {
+    /// Creates a new reader over `reader`.
+    pub fn new(reader: R) -> Self {
+        MmoReader {
+            utterances: MachineReader::new(reader),
+            current: None,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for MmoReader<R> {
+    type Item = Result<Mmo, MachineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let utterance = match self.utterances.next() {
+                None => return self.current.take().map(Ok),
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(utterance)) => utterance,
+            };
+            if let Some(mmo) = &mut self.current {
+                if mmo.document_id() == Some(utterance.document_id()) {
+                    mmo.utterances.push(utterance);
+                    continue;
+                }
+            }
+            let next = Mmo {
+                command_line: self.utterances.command_line.take(),
+                abbreviations: std::mem::take(&mut self.utterances.abbreviations),
+                utterances: vec![utterance],
+            };
+            if let Some(mmo) = self.current.replace(next) {
+                return Some(Ok(mmo));
+            }
+        }
+    }
+}
+
+/// The text of `utterance` covered by `spans`, one word per span.
+fn covered_text(utterance: &Utterance, spans: &[Span]) -> Option<String> {
+    let chars = utterance.text.chars().collect::<Vec<_>>();
+    let words = spans
+        .iter()
+        .map(|span| {
+            let start = usize::try_from(span.start.checked_sub(utterance.start)?).ok()?;
+            let end = start.checked_add(usize::try_from(span.length).ok()?)?;
+            chars.get(start..end).map(|c| c.iter().collect::<String>())
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(words.join(" "))
+}
+
+/// Part of speech of the syntax unit containing the first matched word.
+fn part_of_speech(phrase: &Phrase, candidate: &Candidate) -> String {
+    let first = candidate.matched_words.first();
+    phrase
+        .syntax
+        .iter()
+        .find(|unit| {
+            first.is_some_and(|word| {
+                unit.input_match
+                    .iter()
+                    .any(|w| w.eq_ignore_ascii_case(word))
+            })
+        })
+        .and_then(|unit| unit.tag.clone())
+        .unwrap_or_default()
+}
+
+/// Projects the utterances of one document onto MMI-style concept records.
+///
+/// Every candidate of every mapping becomes a trigger of the record for its
+/// CUI. Since MetaMap's MMI ranking cannot be reproduced here, the score of a
+/// record is its best mapping score (as 0-1000), and tree codes are left empty.
+/// The document id is taken from the first utterance.
+pub fn to_mmi_outputs(utterances: &[Utterance]) -> Vec<MmiOutput> {
+    let id = utterances.first().map_or("", Utterance::document_id);
+    let mut matches = Vec::new();
+    for utterance in utterances {
+        let section = utterance.section().unwrap_or(Section::TX);
+        for phrase in &utterance.phrases {
+            for candidate in phrase.mappings.iter().flat_map(|m| &m.candidates) {
+                matches.push(ConceptMatch {
+                    cui: Some(candidate.cui.clone()),
+                    preferred_name: candidate.preferred_name.clone(),
+                    matched: candidate.matched.clone(),
+                    semantic_types: candidate.semantic_types.clone(),
+                    score: f64::from(candidate.score.abs()),
+                    section: section.clone(),
+                    utterance_number: utterance.number().unwrap_or(1),
+                    text: covered_text(utterance, &candidate.positions)
+                        .unwrap_or_else(|| candidate.matched_words.join(" ")),
+                    part_of_speech: part_of_speech(phrase, candidate),
+                    negated: candidate.negated,
+                    spans: candidate.positions.clone(),
+                });
+            }
+        }
+    }
+    aggregate(id, matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"args('MetaMap16.Linux -q',[machine_output-[]]).
+aas([]).
+neg_list([]).
+utterance('24119710.ti.1',"Effects of heart attack",0/23,[]).
+phrase('Effects',[head([lexmatch([effects]),inputmatch(['Effects']),tag(noun),tokens([effects])])],0/7,[]).
+candidates(1,0,0,1,[ev(-1000,'C1280500','Effects','Effect',[effects],[qlco],[[[1,1],[1,1],0]],yes,no,['MTH'],[0/7],0,0)]).
+mappings([map(-1000,[ev(-1000,'C1280500','Effects','Effect',[effects],[qlco],[[[1,1],[1,1],0]],yes,no,['MTH'],[0/7],0,0)])]).
+phrase('of heart attack',[prep([lexmatch([of]),inputmatch([of]),tag(prep),tokens([of])]),head([lexmatch(['heart attack']),inputmatch([heart,attack]),tag(noun),tokens([heart,attack])])],8/15,[]).
+candidates(1,0,0,1,[
+ev(-1000,'C0027051','Heart attack','Myocardial Infarction',[heart,attack],[dsyn],[[[1,2],[1,2],0]],yes,no,['MSH','MTH'],[11/12],0,0)
+]).
+mappings([map(-1000,[ev(-1000,'C0027051','Heart attack','Myocardial Infarction',[heart,attack],[dsyn],[[[1,2],[1,2],0]],yes,no,['MSH','MTH'],[11/12],0,0)])]).
+'EOU'.
+utterance('24119710.ab.1',"No heart attack isn't here.",24/27,[]).
+phrase('No heart attack',[det([lexmatch([no]),inputmatch(['No']),tag(det),tokens([no])]),head([lexmatch(['heart attack']),inputmatch([heart,attack]),tag(noun),tokens([heart,attack])])],24/15,[]).
+candidates([ev(-861,'C0027051','Heart attack','Myocardial Infarction',[heart,attack],[dsyn],[[[1,2],[1,2],0]],yes,no,['MSH'],[27/5,33/6],0,1)]).
+mappings([map(-861,[ev(-861,'C0027051','Heart attack','Myocardial Infarction',[heart,attack],[dsyn],[[[1,2],[1,2],0]],yes,no,['MSH'],[27/5,33/6],0,1)])]).
+'EOU'.
+"#;
+
+    #[test]
+    fn test_parse_term() {
+        let term = parse_term("ev(-1000,'C1','it''s',\"a \\\"b\\\"\",[x],[0/7],1.5).").unwrap();
+        assert_eq!(term.name(), Some("ev"));
+        let args = term.args();
+        assert_eq!(args[0], Term::Int(-1000));
+        assert_eq!(args[2], Term::Atom("it's".to_string()));
+        assert_eq!(args[3], Term::Str("a \"b\"".to_string()));
+        assert_eq!(args[5].as_list().unwrap()[0].as_position(), Some((0, 7)));
+        assert_eq!(args[6], Term::Float(1.5));
+        assert_eq!(parse_term("'EOU'.").unwrap(), Term::Atom("EOU".to_string()));
+        assert!(parse_term("foo(bar").unwrap_err().offset >= 7);
+        assert!(parse_term("foo(bar)").is_err());
+        assert!(parse_term("foo(bar)). x").is_err());
+    }
+
+    #[test]
+    fn test_parse_term_depth() {
+        let nested = |depth: usize, open: &str, close: &str| {
+            format!("{}x{}.", open.repeat(depth), close.repeat(depth))
+        };
+        assert!(parse_term(&nested(MAX_DEPTH, "[", "]")).is_ok());
+        assert!(parse_term(&nested(MAX_DEPTH, "f(", ")")).is_ok());
+        for (open, close) in [("[", "]"), ("f(", ")"), ("(", ")")] {
+            let error = parse_term(&nested(MAX_DEPTH + 1, open, close)).unwrap_err();
+            assert_eq!(error.message, "terms are nested too deeply");
+        }
+        let error = parse_term(&format!("{}.", "[".repeat(200_000))).unwrap_err();
+        assert_eq!(error.offset, MAX_DEPTH + 1);
+        assert!(parse_term(&format!("x{}.", "*x".repeat(MAX_DEPTH))).is_ok());
+        let error = parse_term(&format!("[x{}].", "*x".repeat(200_000))).unwrap_err();
+        assert_eq!(error.message, "terms are nested too deeply");
+    }
+
+    #[test]
+    fn test_machine_reader() {
+        let utterances = MachineReader::new(SAMPLE.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(utterances.len(), 2);
+        let first = &utterances[0];
+        assert_eq!(first.document_id(), "24119710");
+        assert_eq!(first.section(), Some(Section::TI));
+        assert_eq!(first.number(), Some(1));
+        assert_eq!(first.text, "Effects of heart attack");
+        assert_eq!(first.phrases.len(), 2);
+        let phrase = &first.phrases[1];
+        assert_eq!(phrase.syntax[1].kind, "head");
+        assert_eq!(phrase.syntax[1].input_match, ["heart", "attack"]);
+        assert_eq!(phrase.syntax[1].tag.as_deref(), Some("noun"));
+        let candidate = &phrase.candidates[0];
+        assert_eq!(candidate.cui, "C0027051");
+        assert_eq!(candidate.preferred_name, "Myocardial Infarction");
+        assert_eq!(candidate.sources, ["MSH", "MTH"]);
+        assert_eq!(candidate.positions, [Span::new(11, 12)]);
+        assert!(candidate.head && !candidate.overmatch && !candidate.negated);
+        assert_eq!(phrase.mappings[0].candidates[0], *candidate);
+        assert!(utterances[1].phrases[0].candidates[0].negated);
+    }
+
+    #[test]
+    fn test_machine_reader_errors() {
+        let input = "utterance('1.tx.1',\"a\",0/1,[]).\nphrase(oops.\nphrase('a',[],0/1,[]).\ncandidates(nope).\n'EOU'.\n";
+        let items = MachineReader::new(input.as_bytes()).collect::<Vec<_>>();
+        assert_eq!(items.len(), 3);
+        assert!(matches!(
+            items[0],
+            Err(MachineError::Syntax { line_number: 2, .. })
+        ));
+        assert!(matches!(
+            items[1],
+            Err(MachineError::Structure { line_number: 4, .. })
+        ));
+        assert_eq!(items[2].as_ref().unwrap().phrases.len(), 1);
+
+        // numbers that do not fit the parsed types are errors rather than wrapping
+        let input = "utterance('1.tx.1',\"a\",0/4294967297,[]).\n'EOU'.\n";
+        let items = MachineReader::new(input.as_bytes()).collect::<Vec<_>>();
+        assert!(matches!(
+            items[0],
+            Err(MachineError::Structure { line_number: 1, .. })
+        ));
+        assert!(looks_like_machine_output("\nargs('MetaMap',[])."));
+        assert!(!looks_like_machine_output("1|MMI|..."));
+    }
+
+    #[test]
+    fn test_machine_reader_resync() {
+        let input = "utterance('1.tx.1',\"a\",0/1,[]).\nphrase('a',[],\n0/1 0/1,[]).\nleftover, 'x' ]).\nphrase('b',[],0/1,[]).\n'EOU'.\n";
+        let items = MachineReader::new(input.as_bytes()).collect::<Vec<_>>();
+        assert_eq!(items.len(), 2);
+        assert!(matches!(
+            items[0],
+            Err(MachineError::Syntax { line_number: 2, .. })
+        ));
+        assert_eq!(items[1].as_ref().unwrap().phrases[0].text, "b");
+
+        let input = format!(
+            "utterance('1.tx.1',\"a\",0/1,[]).\ncandidates([\n{}]).\n'EOU'.\n",
+            "ev,\n".repeat(MAX_TERM_LINES)
+        );
+        let items = MachineReader::new(input.as_bytes()).collect::<Vec<_>>();
+        assert_eq!(items.len(), 2);
+        match &items[0] {
+            Err(MachineError::Syntax { line_number, error }) => {
+                assert_eq!(*line_number, 2);
+                assert_eq!(error.message, "term is too long");
+            }
+            item => panic!("unexpected {:?}", item),
+        }
+        assert!(items[1].is_ok());
+    }
+
+    #[test]
+    fn test_covered_text() {
+        let utterance = Utterance {
+            id: "1.ti.1".to_string(),
+            text: "Effects of heart attack".to_string(),
+            start: 10,
+            length: 23,
+            phrases: Vec::new(),
+        };
+        let covered = |spans: &[Span]| covered_text(&utterance, spans);
+        assert_eq!(
+            covered(&[Span::new(21, 5), Span::new(27, 6)]).as_deref(),
+            Some("heart attack")
+        );
+        assert_eq!(covered(&[Span::new(5, 2)]), None);
+        assert_eq!(covered(&[Span::new(30, 5)]), None);
+        assert_eq!(covered(&[Span::new(i32::MIN, 1)]), None);
+        assert_eq!(covered(&[Span::new(20, i32::MAX)]), None);
+        let utterance = Utterance {
+            start: -10,
+            ..utterance
+        };
+        assert_eq!(covered_text(&utterance, &[Span::new(i32::MAX, 1)]), None);
+    }
+
+    #[test]
+    fn test_to_mmi_outputs() {
+        let utterances = MachineReader::new(SAMPLE.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let records = to_mmi_outputs(&utterances);
+        assert_eq!(records.len(), 2);
+        let effect = &records[0];
+        assert_eq!(effect.id.as_str(), "24119710");
+        assert_eq!(effect.cui.as_str(), "C1280500");
+        assert_eq!(
+            effect.to_string(),
+            "24119710|MMI|1000.00|Effect|C1280500|[qlco]|[\"Effects\"-ti-1-\"Effects\"-noun-0]|TI|0/7|"
+        );
+        let heart = &records[1];
+        assert_eq!(heart.score, 1000.0);
+        assert_eq!(heart.location.to_string(), "TI;AB");
+        assert_eq!(heart.triggers.len(), 2);
+        assert_eq!(heart.triggers[1].text, "heart attack");
+        assert!(heart.triggers[1].negation);
+        assert_eq!(heart.positional_info.to_string(), "11/12;27/5,33/6");
+    }
+
+    #[test]
+    fn test_mmo_reader() {
+        let second = "args('MetaMap16.Linux -q',[]).\naas(['MI'*'myocardial infarction'*[1,2,2,21,30]*['C0027051']]).\nutterance('2.ab.1',\"MI.\",0/3,[]).\n'EOU'.\n";
+        let input = format!("{}{}", SAMPLE, second);
+        let documents = MmoReader::new(input.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(documents.len(), 2);
+        let first = &documents[0];
+        assert_eq!(first.document_id(), Some("24119710"));
+        assert_eq!(first.command_line.as_deref(), Some("MetaMap16.Linux -q"));
+        assert!(first.abbreviations.is_empty());
+        assert_eq!(first.utterances.len(), 2);
+        let abbreviation = &documents[1].abbreviations[0];
+        assert_eq!(abbreviation.short_form, "MI");
+        assert_eq!(abbreviation.long_form, "myocardial infarction");
+        assert_eq!(abbreviation.long_length, 21);
+        assert_eq!(abbreviation.start, 30);
+        let outputs = documents[1].to_outputs();
+        match &outputs[..] {
+            [Output::AA(aa)] => {
+                assert_eq!(
+                    aa.to_string(),
+                    "2|AA|MI|myocardial infarction|1|2|2|21|30:2"
+                );
+            }
+            outputs => panic!("unexpected {:?}", outputs),
+        }
+
+        let input = "aas(['MI'*'myocardial infarction'*[1]]).\nutterance('2.ab.1',\"MI.\",0/3,[]).\n'EOU'.\n";
+        let items = MmoReader::new(input.as_bytes()).collect::<Vec<_>>();
+        assert!(matches!(
+            items[0],
+            Err(MachineError::Structure { line_number: 1, .. })
+        ));
+        assert!(items[1].as_ref().unwrap().abbreviations.is_empty());
+    }
+}
@@ -5,7 +5,8 @@
 //! ```bash
 //! mmi_parser data
 //! ```
-//! which would parse all of the `.txt` files inside your data directory.
+//! which would parse all of the `.txt` files inside your data directory, while
+//! `mmi_parser -` reads from stdin and writes to stdout.
 //!
 //! The output of the program is a 1:1 mapping where a new file is created for each
 //! file that is parsed.  This helps maintain indexing integrity when scanning MetaMap output.
@@ -13,30 +14,267 @@
 //! also maintains the integrity of linking each line with its original fielded MMI output.
 //! The output files have the same title as their .txt counterparts plus
 //! a `_parsed` label to ensure clarity that they represent parsed data.
+//! With `--format csv` or `--format tsv` the records are written as flat tables
+//! instead, with AA records in a separate `_parsed_aa` file.
 
 use std::error::Error;
 use std::fs::{self, File};
-use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::io::{self, BufRead, BufReader, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::Mutex;
+use std::thread;
 
 use colored::*;
 
 use clap::Parser;
 use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
 use walkdir::WalkDir;
 
 /// A simple program to parse fielded MMI output from txt into jsonl.
 ///
-/// Expects to find `.txt` files inside the provided <FOLDER> and will
+/// Expects to find `.txt` files (or `.xml` and `.json` files with
+/// `--input-format xml` and `json`) inside the provided <FOLDER> and will
 /// scan each line of MMI output from each file and transfer it to
 /// a single line of json inside a parsed jsonlines file with the same name.
+/// With `--recursive` the folders inside <FOLDER> are read as well, and
+/// `--output-dir` keeps the output files apart from the input files.
 ///
 /// For more information see the [README](https://github.com/UK-IPOP/mmi-parser-rs) or the
 /// [API Docs](https://docs.rs/mmi-parser/latest/mmi_parser/)
 #[derive(Parser, Debug)]
 #[clap(author, version)]
 struct Cli {
-    /// Folder to read files from
+    /// Folder to read files from, or `-` to read from stdin and write json lines
+    /// to stdout
     folder: String,
+    /// Also read the files of all folders inside <FOLDER>
+    #[clap(short, long)]
+    recursive: bool,
+    /// Write output files into this folder instead of next to the input files,
+    /// mirroring the folder structure of <FOLDER>
+    #[clap(short, long)]
+    output_dir: Option<String>,
+    /// Show detailed parser diagnostics on stderr
+    #[clap(short, long)]
+    verbose: bool,
+    /// Add the occurrences each trigger produced to the triggers of MMI records
+    #[clap(long)]
+    trigger_spans: bool,
+    /// Write TI, AB, TX and TI;AB locations as the strings older releases used
+    /// ("TI", "AB", "TX", "Tiab") instead of lists of sections
+    #[clap(long)]
+    legacy_locations: bool,
+    /// Replace the semantic type abbreviations of MMI records with objects that
+    /// also hold their TUI, full name and semantic group
+    #[clap(long)]
+    expand_semantic_types: bool,
+    /// Keep records with a malformed id, CUI or tree code instead of skipping
+    /// them, reporting the problems as warnings
+    #[clap(long)]
+    lenient: bool,
+    /// Skip the lines of fielded MMI files matching this regular expression, on
+    /// top of the blank lines, timestamps, MetaMap banners and `--sldi` input
+    /// echoes always skipped.
+    /// Can be given more than once
+    #[clap(long = "skip-pattern", value_name = "REGEX")]
+    skip_patterns: Vec<regex::Regex>,
+    /// Write one json object per document (consecutive lines sharing an id)
+    /// holding its `mmi` and `aa` records, instead of one per line
+    #[clap(long)]
+    per_document: bool,
+    /// Format of the input files: `mmi` (fielded MMI), `lite` (fielded MMI written
+    /// by MetaMap Lite), `machine` (Prolog machine output), `xml` (XML output),
+    /// `json` (JSON output), `human` (default human-readable output) or `auto` to
+    /// detect it from the start of each file
+    #[clap(long, default_value = "auto")]
+    input_format: InputFormat,
+    /// Format of the output files: `json` (jsonlines), `csv`, `tsv`, `parquet` or
+    /// `sqlite` (when built with the feature of the same name). Tables hold the
+    /// MMI records (see `--explode`), with AA records in a separate `_parsed_aa`
+    /// table. `sqlite` writes a single database, `parsed.sqlite` in <FOLDER>
+    /// unless `--combined` is given. The json-specific options only apply to json
+    #[clap(long, default_value = "json")]
+    format: OutputFormat,
+    /// How MMI records are spread over table rows: `joined` (one row per record,
+    /// with triggers and positions written as in MMI output), `triggers` (one row
+    /// per trigger) or `positions` (one row per span)
+    #[clap(long, default_value = "joined")]
+    explode: mmi_parser::rows::Explode,
+    /// Write the records of all files into <COMBINED> instead of one output file
+    /// per input (AA records of tables go next to it, with `_aa` added to its name)
+    #[clap(long)]
+    combined: Option<String>,
+    /// Number of files to parse at the same time, 0 using all cores. Outputs are
+    /// still written in input order, also with `--combined`
+    #[clap(short, long, default_value = "1")]
+    jobs: usize,
+}
+
+/// MetaMap output formats the tool can read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputFormat {
+    Auto,
+    Mmi,
+    Lite,
+    Machine,
+    Xml,
+    Json,
+    Human,
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(InputFormat::Auto),
+            "mmi" => Ok(InputFormat::Mmi),
+            "lite" => Ok(InputFormat::Lite),
+            "machine" => Ok(InputFormat::Machine),
+            "xml" => Ok(InputFormat::Xml),
+            "json" => Ok(InputFormat::Json),
+            "human" => Ok(InputFormat::Human),
+            _ => Err(format!(
+                "unknown input format `{}`, expected auto, mmi, lite, machine, xml, json or human",
+                s
+            )),
+        }
+    }
+}
+
+impl InputFormat {
+    /// Extension of the files read from <FOLDER>: `.xml` and `.json` for XML and
+    /// JSON output, `.txt` for the other formats and when detecting the format.
+    fn extension(self) -> &'static str {
+        match self {
+            InputFormat::Xml => "xml",
+            InputFormat::Json => "json",
+            _ => "txt",
+        }
+    }
+
+    /// Resolves `Auto` by looking at the start of `input`, without consuming it.
+    fn detect(self, input: &mut impl BufRead) -> InputFormat {
+        if self != InputFormat::Auto {
+            return self;
+        }
+        let start = input.fill_buf().map(String::from_utf8_lossy);
+        match start {
+            Ok(start) if mmi_parser::machine::looks_like_machine_output(&start) => {
+                InputFormat::Machine
+            }
+            Ok(start) if mmi_parser::xml::looks_like_xml_output(&start) => InputFormat::Xml,
+            Ok(start) if mmi_parser::json::looks_like_json_output(&start) => InputFormat::Json,
+            // fielded MMI may start with the `Processing` banners of human output
+            Ok(start) if looks_like_fielded_output(&start) => InputFormat::Mmi,
+            Ok(start) if mmi_parser::human::looks_like_human_output(&start) => InputFormat::Human,
+            _ => InputFormat::Mmi,
+        }
+    }
+}
+
+/// Whether `text` holds MMI or AA records of fielded output.
+fn looks_like_fielded_output(text: &str) -> bool {
+    text.lines()
+        .any(|line| line.contains("|MMI|") || line.contains("|AA|"))
+}
+
+/// Formats the tool can write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Json,
+    Csv,
+    Tsv,
+    #[cfg(feature = "parquet")]
+    Parquet,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(OutputFormat::Parquet),
+            #[cfg(not(feature = "parquet"))]
+            "parquet" => Err("parquet output needs the `parquet` feature".to_string()),
+            #[cfg(feature = "sqlite")]
+            "sqlite" => Ok(OutputFormat::Sqlite),
+            #[cfg(not(feature = "sqlite"))]
+            "sqlite" => Err("sqlite output needs the `sqlite` feature".to_string()),
+            _ => Err(format!(
+                "unknown output format `{}`, expected json, csv, tsv, parquet or sqlite",
+                s
+            )),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Name of the single file formats that do not write one file per input
+    /// write into the input folder.
+    fn single_file(self) -> Option<&'static str> {
+        match self {
+            #[cfg(feature = "sqlite")]
+            OutputFormat::Sqlite => Some("parsed.sqlite"),
+            _ => None,
+        }
+    }
+
+    /// Suffix replacing `.txt` (or added after other extensions) in the names of output files.
+    fn suffix(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "_parsed.jsonl",
+            OutputFormat::Csv => "_parsed.csv",
+            OutputFormat::Tsv => "_parsed.tsv",
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => "_parsed.parquet",
+            #[cfg(feature = "sqlite")]
+            OutputFormat::Sqlite => "_parsed.sqlite",
+        }
+    }
+}
+
+/// Writes library diagnostics to stderr using the CLI's colored formatting.
+struct StderrLogger;
+
+static LOGGER: StderrLogger = StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let label = match record.level() {
+                log::Level::Error => "ERROR".red().bold(),
+                log::Level::Warn => "WARNING".yellow().bold(),
+                _ => "DEBUG".dimmed(),
+            };
+            eprintln!("{} {}", label, record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn initialize_logging(verbose: bool) {
+    // only fails if a logger was already set, in which case keep that one
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(if verbose {
+            log::LevelFilter::Debug
+        } else {
+            log::LevelFilter::Warn
+        });
+    }
 }
 
 fn initialize_progress(items: u64) -> ProgressBar {
@@ -51,22 +289,816 @@ fn initialize_progress(items: u64) -> ProgressBar {
     pb
 }
 
-fn get_total_files(target_folder: &str) -> Result<usize, Box<dyn Error>> {
-    let walker = WalkDir::new(target_folder);
-    let mut file_count = 0;
-    for e in walker.into_iter() {
-        let entry = e?;
-        let fname = entry.file_name().to_str().ok_or(std::fmt::Error)?;
-        if fname.ends_with(".txt") {
-            file_count += 1
+/// Converts a parsed record into json, applying the output options from `cli`.
+///
+/// With `--trigger-spans` each trigger of MMI records gets an `occurrences` list
+/// (see [`mmi_parser::MmiOutput::trigger_spans`]). With `--legacy-locations`
+/// locations that have a [legacy name](mmi_parser::Location::legacy_name) are
+/// written using it. With `--expand-semantic-types` semantic types are looked up
+/// in the built-in [table](mmi_parser::SemanticType); unknown ones only keep
+/// their abbreviation.
+fn to_json(output: &mmi_parser::Output, cli: &Cli) -> serde_json::Value {
+    match output {
+        mmi_parser::Output::MMI(mmi) => serde_json::json!({ "MMI": mmi_to_json(mmi, cli) }),
+        mmi_parser::Output::AA(_) => {
+            serde_json::to_value(output).expect("unable to serialize json")
         }
     }
-    Ok(file_count)
+}
+
+/// Converts a parsed MMI record into json, see [`to_json`].
+fn mmi_to_json(mmi: &mmi_parser::MmiOutput, cli: &Cli) -> serde_json::Value {
+    let mut record = serde_json::to_value(mmi).expect("unable to serialize json");
+    if cli.trigger_spans {
+        let spans = mmi.trigger_spans();
+        if let Some(triggers) = record["triggers"].as_array_mut() {
+            for (trigger, occurrences) in triggers.iter_mut().zip(spans) {
+                trigger["occurrences"] =
+                    serde_json::to_value(occurrences).expect("unable to serialize json");
+            }
+        }
+    }
+    if cli.expand_semantic_types {
+        let details = mmi
+            .semantic_types
+            .iter()
+            .zip(mmi.semantic_type_details())
+            .map(|(abbreviation, detail)| match detail {
+                Some(st) => serde_json::to_value(st).expect("unable to serialize json"),
+                None => serde_json::json!({ "abbreviation": abbreviation }),
+            })
+            .collect();
+        record["semantic_types"] = serde_json::Value::Array(details);
+    }
+    if cli.legacy_locations {
+        if let Some(name) = mmi.location.legacy_name() {
+            record["location"] = name.into();
+        }
+        if let Some(triggers) = record["triggers"].as_array_mut() {
+            for (trigger, source) in triggers.iter_mut().zip(&mmi.triggers) {
+                if let Some(name) = source.loc.legacy_name() {
+                    trigger["loc"] = name.into();
+                }
+            }
+        }
+    }
+    record
+}
+
+/// Converts a document into json, applying the same options as [`to_json`]
+/// to each of its MMI records.
+fn document_to_json(document: &mmi_parser::Document, cli: &Cli) -> serde_json::Value {
+    let mmi = document
+        .mmi
+        .iter()
+        .map(|x| mmi_to_json(x, cli))
+        .collect::<Vec<_>>();
+    serde_json::json!({ "id": document.id, "mmi": mmi, "aa": document.aa })
+}
+
+fn write_json(writer: &mut impl Write, json_val: &serde_json::Value) -> io::Result<()> {
+    let json_string = serde_json::to_string(json_val).expect("unable to deserialize json");
+    writer.write_all(json_string.as_bytes())?;
+    writer.write_all(b"\n")
+}
+
+/// Whether writing failed because the reader of a pipe (e.g. `head`) went away.
+fn is_broken_pipe(error: &(dyn Error + 'static)) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+/// Stops the tool if a record could not be written: quietly when writing into
+/// a closed pipe, as there is nobody left to write to, and with an error on
+/// stderr otherwise.
+fn check_written(written: Result<(), Box<dyn Error>>) {
+    if let Err(e) = written {
+        if is_broken_pipe(e.as_ref()) {
+            std::process::exit(0);
+        }
+        eprintln!("{} could not write record: {}", "ERROR".red().bold(), e);
+        eprintln!("Exiting...");
+        std::process::exit(1);
+    }
+}
+
+/// Writes records as CSV/TSV rows, MMI records into one table and AA records
+/// into another that is only created once the first AA record comes along.
+struct TableWriter {
+    mmi: csv::Writer<File>,
+    aa: Option<csv::Writer<File>>,
+    aa_path: String,
+    delimiter: u8,
+    explode: mmi_parser::rows::Explode,
+}
+
+impl TableWriter {
+    fn new(
+        path: &str,
+        aa_path: String,
+        delimiter: u8,
+        explode: mmi_parser::rows::Explode,
+    ) -> csv::Result<Self> {
+        let mut mmi = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_path(path)?;
+        mmi.write_record(mmi_parser::rows::mmi_header(explode))?;
+        Ok(TableWriter {
+            mmi,
+            aa: None,
+            aa_path,
+            delimiter,
+            explode,
+        })
+    }
+
+    fn write_mmi(&mut self, mmi: &mmi_parser::MmiOutput) -> csv::Result<()> {
+        for row in mmi_parser::rows::mmi_rows(mmi, self.explode) {
+            self.mmi.write_record(row)?;
+        }
+        Ok(())
+    }
+
+    fn write_aa(&mut self, aa: &mmi_parser::AaOutput) -> csv::Result<()> {
+        let writer = match &mut self.aa {
+            Some(writer) => writer,
+            None => {
+                let mut writer = csv::WriterBuilder::new()
+                    .delimiter(self.delimiter)
+                    .from_path(&self.aa_path)?;
+                writer.write_record(mmi_parser::rows::AA_COLUMNS)?;
+                self.aa.insert(writer)
+            }
+        };
+        writer.write_record(mmi_parser::rows::aa_row(aa))
+    }
+}
+
+/// Number of records collected before they are written as a Parquet row group.
+#[cfg(feature = "parquet")]
+const BATCH_SIZE: usize = 8192;
+
+/// Writes records into Parquet files, MMI records into one file and AA records
+/// into another that is only created once the first AA record comes along.
+#[cfg(feature = "parquet")]
+struct ParquetWriter {
+    builder: mmi_parser::columnar::BatchBuilder,
+    mmi: parquet::arrow::ArrowWriter<File>,
+    aa: Option<parquet::arrow::ArrowWriter<File>>,
+    aa_path: String,
+}
+
+#[cfg(feature = "parquet")]
+impl ParquetWriter {
+    fn new(path: &str, aa_path: String) -> Result<Self, Box<dyn Error>> {
+        let mmi = parquet::arrow::ArrowWriter::try_new(
+            File::create(path)?,
+            mmi_parser::columnar::mmi_schema(),
+            None,
+        )?;
+        Ok(ParquetWriter {
+            builder: mmi_parser::columnar::BatchBuilder::new(),
+            mmi,
+            aa: None,
+            aa_path,
+        })
+    }
+
+    fn write(&mut self, output: mmi_parser::Output) -> Result<(), Box<dyn Error>> {
+        self.builder.push(output);
+        if self.builder.mmi_len() >= BATCH_SIZE {
+            self.flush_mmi()?;
+        }
+        if self.builder.aa_len() >= BATCH_SIZE {
+            self.flush_aa()?;
+        }
+        Ok(())
+    }
+
+    fn flush_mmi(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.builder.mmi_len() > 0 {
+            self.mmi.write(&self.builder.finish_mmi()?)?;
+        }
+        Ok(())
+    }
+
+    fn flush_aa(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.builder.aa_len() == 0 {
+            return Ok(());
+        }
+        let batch = self.builder.finish_aa()?;
+        let writer = match &mut self.aa {
+            Some(writer) => writer,
+            None => self.aa.insert(parquet::arrow::ArrowWriter::try_new(
+                File::create(&self.aa_path)?,
+                mmi_parser::columnar::aa_schema(),
+                None,
+            )?),
+        };
+        writer.write(&batch)?;
+        Ok(())
+    }
+
+    /// Writes the remaining records and the file footers.
+    fn close(mut self) -> Result<(), Box<dyn Error>> {
+        self.flush_mmi()?;
+        self.flush_aa()?;
+        self.mmi.close()?;
+        if let Some(aa) = self.aa {
+            aa.close()?;
+        }
+        Ok(())
+    }
+}
+
+/// A record or document sent through [`RecordWriter::Channel`].
+enum Queued {
+    Record(mmi_parser::Output),
+    Document(mmi_parser::Document),
+}
+
+impl Queued {
+    /// Writes the record or document into `out_writer`.
+    fn write_to(self, out_writer: &mut RecordWriter, cli: &Cli) {
+        match self {
+            Queued::Record(output) => out_writer.write(output, cli),
+            Queued::Document(document) => out_writer.write_document(document, cli),
+        }
+    }
+}
+
+/// Destination of parsed records, either the output of a single input file or
+/// the combined output of all of them, or a channel passing the records of a
+/// file on to the thread writing the combined output.
+enum RecordWriter {
+    Json(LineWriter<Box<dyn Write + Send>>),
+    Channel(SyncSender<Queued>),
+    Table(Box<TableWriter>),
+    #[cfg(feature = "parquet")]
+    Parquet(Box<ParquetWriter>),
+    #[cfg(feature = "sqlite")]
+    Sqlite(Box<mmi_parser::sqlite::SqliteWriter>),
+}
+
+/// Path of the file holding the AA records of tables written to `path`, with
+/// `_aa` added to the file name, e.g. `sample_parsed_aa.csv`.
+fn aa_path(path: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}_aa.{}", stem, extension.to_string_lossy()),
+        None => format!("{}_aa", stem),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+impl RecordWriter {
+    /// Creates the output file(s) at `path` in the format chosen in `cli`.
+    fn create(path: &str, cli: &Cli) -> Result<Self, Box<dyn Error>> {
+        let delimiter = match cli.format {
+            OutputFormat::Json => {
+                let out_file = File::create(path)?;
+                return Ok(RecordWriter::Json(LineWriter::new(Box::new(out_file))));
+            }
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => {
+                let writer = ParquetWriter::new(path, aa_path(path))?;
+                return Ok(RecordWriter::Parquet(Box::new(writer)));
+            }
+            #[cfg(feature = "sqlite")]
+            OutputFormat::Sqlite => {
+                let writer = mmi_parser::sqlite::SqliteWriter::open(path)?;
+                return Ok(RecordWriter::Sqlite(Box::new(writer)));
+            }
+            OutputFormat::Csv => b',',
+            OutputFormat::Tsv => b'\t',
+        };
+        let table = TableWriter::new(path, aa_path(path), delimiter, cli.explode)?;
+        Ok(RecordWriter::Table(Box::new(table)))
+    }
+
+    /// Writes a single record.
+    fn write(&mut self, output: mmi_parser::Output, cli: &Cli) {
+        let written: Result<(), Box<dyn Error>> = match (self, &output) {
+            (RecordWriter::Json(writer), _) => {
+                write_json(writer, &to_json(&output, cli)).map_err(Into::into)
+            }
+            (RecordWriter::Channel(sender), _) => send(sender, Queued::Record(output)),
+            (RecordWriter::Table(table), mmi_parser::Output::MMI(mmi)) => {
+                table.write_mmi(mmi).map_err(Into::into)
+            }
+            (RecordWriter::Table(table), mmi_parser::Output::AA(aa)) => {
+                table.write_aa(aa).map_err(Into::into)
+            }
+            #[cfg(feature = "parquet")]
+            (RecordWriter::Parquet(writer), _) => writer.write(output),
+            #[cfg(feature = "sqlite")]
+            (RecordWriter::Sqlite(writer), _) => writer.write(&output).map_err(Into::into),
+        };
+        check_written(written);
+    }
+
+    /// Writes a whole document, as a single json object or as its records.
+    fn write_document(&mut self, document: mmi_parser::Document, cli: &Cli) {
+        match self {
+            RecordWriter::Json(writer) => {
+                let written = write_json(writer, &document_to_json(&document, cli));
+                return check_written(written.map_err(Into::into));
+            }
+            RecordWriter::Channel(sender) => {
+                return check_written(send(sender, Queued::Document(document)));
+            }
+            _ => {}
+        }
+        let outputs = document.mmi.into_iter().map(mmi_parser::Output::MMI);
+        let outputs = outputs.chain(document.aa.into_iter().map(mmi_parser::Output::AA));
+        for output in outputs {
+            self.write(output, cli);
+        }
+    }
+
+    /// Flushes everything written so far and completes the output files.
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        match self {
+            RecordWriter::Json(mut writer) => writer.flush()?,
+            RecordWriter::Channel(_) => {}
+            RecordWriter::Table(mut table) => {
+                table.mmi.flush()?;
+                if let Some(aa) = &mut table.aa {
+                    aa.flush()?;
+                }
+            }
+            #[cfg(feature = "parquet")]
+            RecordWriter::Parquet(writer) => writer.close()?,
+            #[cfg(feature = "sqlite")]
+            RecordWriter::Sqlite(writer) => {
+                writer.finish()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Creates the output file(s) at `path` along with any missing directories,
+/// exiting if that is not possible.
+fn create_writer(path: &str, filename: &str, cli: &Cli) -> RecordWriter {
+    let created = Path::new(path)
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map_or(Ok(()), fs::create_dir_all)
+        .map_err(Into::into)
+        .and_then(|_| RecordWriter::create(path, cli));
+    match created {
+        Ok(writer) => writer,
+        Err(_) => {
+            eprintln!("Could not create output file for {}.", filename);
+            eprintln!("Exiting...");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn report_error(error: mmi_parser::ReadError, file_name: &str) {
+    match error {
+        mmi_parser::ReadError::Parse {
+            line_number,
+            line,
+            error,
+            ..
+        } => {
+            eprintln!(
+                "{err} in {file_name} on line {line_number}: {error}",
+                err = "ERROR".red().bold(),
+                file_name = file_name,
+                line_number = line_number,
+                error = error
+            );
+            eprintln!("Line contents: {}", &line);
+            eprintln!("Skipping line...");
+        }
+        e => {
+            eprintln!(
+                "{err} in {file_name}: {error}",
+                err = "ERROR".red().bold(),
+                file_name = file_name,
+                error = e
+            );
+        }
+    }
+}
+
+/// Lists the files with the given `extension` directly inside `folder`, or
+/// anywhere below it when `recursive`, in a stable order.
+///
+/// Entries that cannot be read (such as folders without permission) are
+/// reported and skipped. Fails only if `folder` itself cannot be read.
+fn find_input_files(
+    folder: &str,
+    recursive: bool,
+    extension: &str,
+) -> Result<Vec<PathBuf>, walkdir::Error> {
+    let walker = WalkDir::new(folder)
+        .min_depth(1)
+        .max_depth(if recursive { usize::MAX } else { 1 })
+        .sort_by_file_name();
+    let mut files = Vec::new();
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) if e.depth() == 0 => return Err(e),
+            Err(e) => {
+                eprintln!(
+                    "{warn} could not read {path}: {error}",
+                    warn = "WARNING".yellow().bold(),
+                    path = e.path().unwrap_or(Path::new(folder)).display(),
+                    error = e
+                );
+                eprintln!("Skipping...");
+                continue;
+            }
+        };
+        let is_input =
+            entry.file_type().is_file() && entry.path().extension().is_some_and(|e| e == extension);
+        if is_input {
+            files.push(entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+/// Path of the output file for the input file at `path`: next to it, or at the
+/// same place relative to `--output-dir` as the input is relative to <FOLDER>.
+///
+/// The suffix replaces the `.txt` extension, while other extensions are kept
+/// (`sample.xml` becoming `sample.xml_parsed.jsonl`) so that inputs sharing a
+/// name never share an output.
+fn output_path(path: &Path, cli: &Cli) -> String {
+    let path = match &cli.output_dir {
+        Some(dir) => Path::new(dir).join(path.strip_prefix(&cli.folder).unwrap_or(path)),
+        None => path.to_path_buf(),
+    };
+    let stem = if path.extension().is_some_and(|e| e == "txt") {
+        path.file_stem()
+    } else {
+        path.file_name()
+    };
+    let stem = stem.unwrap_or_default().to_string_lossy();
+    let name = format!("{}{}", stem, cli.format.suffix());
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// Writes the records of a fielded MMI file.
+fn convert_mmi(
+    input: impl BufRead,
+    dialect: mmi_parser::Dialect,
+    out_writer: &mut RecordWriter,
+    filename: &str,
+    cli: &Cli,
+) {
+    let strictness = if cli.lenient {
+        mmi_parser::Strictness::Lenient
+    } else {
+        mmi_parser::Strictness::Strict
+    };
+    let mut reader = mmi_parser::MmiReader::new(input)
+        .with_strictness(strictness)
+        .with_dialect(dialect)
+        .with_skip_patterns(cli.skip_patterns.iter().cloned());
+    if cli.per_document {
+        let mut documents = mmi_parser::DocumentReader::new(reader);
+        for item in documents.by_ref() {
+            match item {
+                Ok(document) => out_writer.write_document(document, cli),
+                Err(e) => report_error(e, filename),
+            }
+        }
+        report_skipped(documents.into_inner().skipped_lines(), filename);
+        return;
+    }
+    for item in reader.by_ref() {
+        match item {
+            Ok(parsed) => {
+                for warning in &parsed.warnings {
+                    eprintln!(
+                        "{warn} in {file_name} on line {line_number}: {warning}",
+                        warn = "WARNING".yellow().bold(),
+                        file_name = filename,
+                        line_number = parsed.line_number,
+                        warning = warning
+                    );
+                }
+                out_writer.write(parsed.output, cli);
+            }
+            Err(e) => report_error(e, filename),
+        }
+    }
+    report_skipped(reader.skipped_lines(), filename);
+}
+
+/// Tells how many lines of a file were skipped for not being records.
+fn report_skipped(skipped: usize, file_name: &str) {
+    if skipped > 0 {
+        eprintln!(
+            "{info} in {file_name}: skipped {skipped} line(s) that are not records",
+            info = "INFO".blue().bold(),
+            file_name = file_name,
+            skipped = skipped
+        );
+    }
+}
+
+/// Writes the records of one document read from a structured format (XML or
+/// JSON output) one by one, or as a single document with `--per-document`.
+fn write_outputs(
+    outputs: Vec<mmi_parser::Output>,
+    id: Option<&str>,
+    out_writer: &mut RecordWriter,
+    filename: &str,
+    cli: &Cli,
+) {
+    if !cli.per_document {
+        for output in outputs {
+            out_writer.write(output, cli);
+        }
+        return;
+    }
+    match id.unwrap_or_default().parse() {
+        Ok(id) => {
+            let mut document = mmi_parser::Document::new(id);
+            for output in outputs {
+                document.push(output);
+            }
+            out_writer.write_document(document, cli);
+        }
+        Err(_) => eprintln!(
+            "{warn} in {file_name}: skipping document without an id",
+            warn = "WARNING".yellow().bold(),
+            file_name = filename
+        ),
+    }
+}
+
+/// Writes the concepts of human-readable output as MMI records in the format
+/// selected with `--format`, one document (consecutive utterances sharing a
+/// document id) at a time.
+fn convert_utterances<U, E: std::fmt::Display>(
+    items: impl Iterator<Item = Result<U, E>>,
+    document_id: impl Fn(&U) -> &str,
+    to_mmi_outputs: impl Fn(&[U]) -> Vec<mmi_parser::MmiOutput>,
+    out_writer: &mut RecordWriter,
+    filename: &str,
+    cli: &Cli,
+) {
+    let mut write_document = |utterances: &[U]| {
+        if let Some(first) = utterances.first() {
+            let outputs = to_mmi_outputs(utterances)
+                .into_iter()
+                .map(mmi_parser::Output::MMI)
+                .collect();
+            write_outputs(outputs, Some(document_id(first)), out_writer, filename, cli);
+        }
+    };
+    let mut utterances: Vec<U> = Vec::new();
+    for item in items {
+        match item {
+            Ok(utterance) => {
+                if let Some(last) = utterances.last() {
+                    if document_id(last) != document_id(&utterance) {
+                        write_document(&utterances);
+                        utterances.clear();
+                    }
+                }
+                utterances.push(utterance);
+            }
+            Err(e) => eprintln!(
+                "{err} in {file_name}: {error}",
+                err = "ERROR".red().bold(),
+                file_name = filename,
+                error = e
+            ),
+        }
+    }
+    write_document(&utterances);
+}
+
+/// Writes the documents of an XML or machine output file in the format
+/// selected with `--format`.
+fn convert_mmos<E: std::fmt::Display>(
+    items: impl Iterator<Item = Result<mmi_parser::machine::Mmo, E>>,
+    out_writer: &mut RecordWriter,
+    filename: &str,
+    cli: &Cli,
+) {
+    for item in items {
+        match item {
+            Ok(mmo) => write_outputs(
+                mmo.to_outputs(),
+                mmo.document_id(),
+                out_writer,
+                filename,
+                cli,
+            ),
+            Err(e) => eprintln!(
+                "{err} in {file_name}: {error}",
+                err = "ERROR".red().bold(),
+                file_name = filename,
+                error = e
+            ),
+        }
+    }
+}
+
+/// Writes the documents of a JSON output file in the format selected with
+/// `--format`.
+fn convert_json_output(
+    input: impl BufRead,
+    out_writer: &mut RecordWriter,
+    filename: &str,
+    cli: &Cli,
+) {
+    for item in mmi_parser::json::JsonReader::new(input) {
+        match item {
+            Ok(document) => write_outputs(
+                document.to_outputs(),
+                document.document_id(),
+                out_writer,
+                filename,
+                cli,
+            ),
+            Err(e) => eprintln!(
+                "{err} in {file_name}: {error}",
+                err = "ERROR".red().bold(),
+                file_name = filename,
+                error = e
+            ),
+        }
+    }
+}
+
+/// Writes the records of an input file in whichever format it is in.
+fn convert_file(mut input: impl BufRead, out_writer: &mut RecordWriter, filename: &str, cli: &Cli) {
+    match cli.input_format.detect(&mut input) {
+        InputFormat::Machine => convert_mmos(
+            mmi_parser::machine::MmoReader::new(input),
+            out_writer,
+            filename,
+            cli,
+        ),
+        InputFormat::Human => convert_utterances(
+            mmi_parser::human::HumanReader::new(input),
+            mmi_parser::human::Utterance::document_id,
+            mmi_parser::human::to_mmi_outputs,
+            out_writer,
+            filename,
+            cli,
+        ),
+        InputFormat::Xml => convert_mmos(
+            mmi_parser::xml::XmlReader::new(input),
+            out_writer,
+            filename,
+            cli,
+        ),
+        InputFormat::Json => convert_json_output(input, out_writer, filename, cli),
+        InputFormat::Lite => {
+            convert_mmi(input, mmi_parser::Dialect::Lite, out_writer, filename, cli)
+        }
+        _ => convert_mmi(
+            input,
+            mmi_parser::Dialect::MetaMap,
+            out_writer,
+            filename,
+            cli,
+        ),
+    }
+}
+
+/// Parses the input file at `path` into its own output file, or into the
+/// `combined` output shared by all files.
+fn process_file(path: &Path, combined: Option<&mut RecordWriter>, cli: &Cli) {
+    let filename = &*path.to_string_lossy();
+    // utilize read lines buffer
+    let input = match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(_) => {
+            eprintln!("Could not open {}.", &filename);
+            eprintln!("Skipping file...");
+            return;
+        }
+    };
+    match combined {
+        None => {
+            let out_file_name = output_path(path, cli);
+            let mut out_writer = create_writer(&out_file_name, filename, cli);
+            convert_file(input, &mut out_writer, filename, cli);
+            finish_writer(out_writer, &out_file_name);
+        }
+        Some(out_writer) => convert_file(input, out_writer, filename, cli),
+    }
+}
+
+/// Sends a record or document to the thread writing the combined output.
+fn send(sender: &SyncSender<Queued>, item: Queued) -> Result<(), Box<dyn Error>> {
+    sender
+        .send(item)
+        .map_err(|_| "the combined output was closed".into())
+}
+
+/// Number of records (or documents) a file may get ahead of the combined
+/// output while the files before it are still being written.
+const COMBINED_BACKLOG: usize = 1024;
+
+/// Parses `files` on `jobs` threads into the combined `out_writer`, in input
+/// order.
+///
+/// Files are started in order, and each one sends its records through a
+/// bounded channel that this thread drains one file after the other. A file
+/// that gets too far ahead waits for its turn, so memory stays bounded; the
+/// file being written was started before any waiting one, so it is always
+/// being parsed.
+fn combine_in_order(
+    files: &[PathBuf],
+    out_writer: &mut RecordWriter,
+    jobs: usize,
+    cli: &Cli,
+    bar: &ProgressBar,
+) {
+    let (senders, receivers): (Vec<_>, Vec<_>) = files
+        .iter()
+        .map(|_| {
+            let (sender, receiver) = mpsc::sync_channel(COMBINED_BACKLOG);
+            (Mutex::new(Some(sender)), receiver)
+        })
+        .unzip();
+    let next = AtomicUsize::new(0);
+    let worker = || loop {
+        let index = next.fetch_add(1, Ordering::Relaxed);
+        let path = match files.get(index) {
+            Some(path) => path,
+            None => return,
+        };
+        let sender = senders[index].lock().unwrap().take();
+        if let Some(sender) = sender {
+            // dropping the writer closes the channel, ending the file
+            process_file(path, Some(&mut RecordWriter::Channel(sender)), cli);
+        }
+    };
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(files.len()) {
+            scope.spawn(worker);
+        }
+        for receiver in receivers {
+            for item in receiver {
+                item.write_to(out_writer, cli);
+            }
+            bar.inc(1);
+        }
+    });
+}
+
+/// Completes the output written to `path`, reporting if that fails.
+fn finish_writer(out_writer: RecordWriter, path: &str) {
+    match out_writer.finish() {
+        Err(e) if !is_broken_pipe(e.as_ref()) => eprintln!(
+            "{err} could not complete {path}: {error}",
+            err = "ERROR".red().bold(),
+            path = path,
+            error = e
+        ),
+        _ => {}
+    }
+}
+
+/// Reads from stdin and writes json lines to stdout (or into the `--combined`
+/// file), printing nothing else to stdout so the tool can be used in pipelines.
+fn parse_stdin(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let filename = "<stdin>";
+    let (mut out_writer, path) = match &cli.combined {
+        Some(path) => (create_writer(path, filename, cli), path.as_str()),
+        None if cli.format == OutputFormat::Json => {
+            let stdout: Box<dyn Write + Send> = Box::new(io::stdout());
+            (RecordWriter::Json(LineWriter::new(stdout)), "<stdout>")
+        }
+        None => {
+            return Err(
+                "only json can be written to stdout, pass --combined <FILE> \
+                        to write other formats into a file"
+                    .into(),
+            )
+        }
+    };
+    convert_file(io::stdin().lock(), &mut out_writer, filename, cli);
+    finish_writer(out_writer, path);
+    Ok(())
 }
 
 /// Main function.
 fn main() -> Result<(), Box<dyn Error>> {
     let cli = Cli::parse();
+    initialize_logging(cli.verbose);
+    if cli.folder == "-" {
+        return parse_stdin(&cli);
+    }
     println!();
     println!("{}", "MMI Parser".cyan().bold());
     println!("{}", "============".cyan().bold());
@@ -76,9 +1108,17 @@ fn main() -> Result<(), Box<dyn Error>> {
         cli.folder.cyan().bold()
     );
 
-    let file_count = get_total_files(&cli.folder);
-    let bar = match file_count {
-        Ok(fc) => initialize_progress(fc as u64),
+    let combined_path = cli.combined.clone().or_else(|| {
+        let name = cli.format.single_file()?;
+        let dir = cli.output_dir.as_deref().unwrap_or(&cli.folder);
+        Some(Path::new(dir).join(name).to_string_lossy().into_owned())
+    });
+    let mut combined = combined_path
+        .as_deref()
+        .map(|path| create_writer(path, path, &cli));
+
+    let files = match find_input_files(&cli.folder, cli.recursive, cli.input_format.extension()) {
+        Ok(files) => files,
         Err(_e) => {
             println!(
                 "{}Had trouble reading some of your files... clean up and try again later?",
@@ -88,68 +1128,42 @@ fn main() -> Result<(), Box<dyn Error>> {
             std::process::exit(1);
         }
     };
+    let bar = initialize_progress(files.len() as u64);
 
-    match fs::read_dir(cli.folder) {
-        Ok(files) => {
-            'files: for file in files {
-                let file = file?;
-                let path = file.path();
-                let filename = path.to_str().expect("could not parse file path");
-                if filename.ends_with(".txt") {
-                    bar.inc(1);
-                    let out_file_name = filename.replace(".txt", "_parsed.jsonl").to_string();
-                    let out_file = fs::File::create(&out_file_name);
-                    if out_file.is_err() {
-                        println!("Could not create output file for {}.", &filename);
-                        println!("Exiting...");
-                        std::process::exit(1);
-                    }
-                    let mut out_writer = LineWriter::new(out_file?);
-                    // utilize read lines buffer
-                    let file = File::open(&path);
-                    if file.is_err() {
-                        println!("Could not open {}.", &filename);
-                        println!("Skipping file...");
-                        continue 'files;
-                    }
-                    let reader = BufReader::new(file?);
-                    'lines: for (i, line) in reader.lines().flatten().enumerate() {
-                        let result = mmi_parser::parse_record(&line);
-                        match result {
-                            Ok(val) => {
-                                let json_val =
-                                    serde_json::to_value(val).expect("unable to serialize json");
-                                let json_string = serde_json::to_string(&json_val)
-                                    .expect("unable to deserialize json");
-                                out_writer.write_all(json_string.as_bytes()).unwrap();
-                                out_writer.write_all(b"\n").unwrap();
-                            }
-                            Err(_e) => {
-                                println!(
-                                    "{err} in {file_name} on line {line_number}.",
-                                    err = "ERROR".red().bold(),
-                                    file_name = &filename,
-                                    line_number = i + i
-                                );
-                                println!("Line contents: {}", &line);
-                                println!("Skipping line...");
-                                continue 'lines;
-                            }
-                        }
-                    }
-                }
+    match combined.as_mut() {
+        Some(out_writer) if cli.jobs == 1 => {
+            for path in &files {
+                process_file(path, Some(&mut *out_writer), &cli);
+                bar.inc(1);
             }
-            bar.finish();
-            println!("Finishing up...");
-            println!("{}", "Done.".bright_green().bold());
-            Ok(())
         }
-        Err(e) => {
-            println!(
-                "{err} Couldn't scan target directory",
-                err = "ERROR".red().bold()
-            );
-            Err(Box::new(e))
+        Some(out_writer) => {
+            let jobs = match cli.jobs {
+                0 => thread::available_parallelism().map_or(1, |n| n.get()),
+                jobs => jobs,
+            };
+            combine_in_order(&files, out_writer, jobs, &cli, &bar);
         }
+        None => {
+            let process = |path: &PathBuf| {
+                process_file(path, None, &cli);
+                bar.inc(1);
+            };
+            if cli.jobs == 1 {
+                files.iter().for_each(process);
+            } else {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(cli.jobs)
+                    .build()?;
+                pool.install(|| files.par_iter().for_each(process));
+            }
+        }
+    }
+    if let (Some(out_writer), Some(path)) = (combined, &combined_path) {
+        finish_writer(out_writer, path);
     }
+    bar.finish();
+    println!("Finishing up...");
+    println!("{}", "Done.".bright_green().bold());
+    Ok(())
 }
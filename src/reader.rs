@@ -0,0 +1,443 @@
+//! Streaming access to fielded MMI output.
+//!
+//! [`MmiReader`] wraps any [`BufRead`] (files, stdin, sockets, decompressed streams)
+//! and parses it one line at a time with [`parse_record`](crate::parse_record), which is exactly
+//! what the command line tool does for each of its input files.
+//!
+//! Lines MetaMap pipelines write around the records (blank lines, timestamp
+//! headers, MetaMap banners and echoed input text) are skipped rather than
+//! reported as errors.
+
+use std::error;
+use std::fmt::{self, Display};
+use std::io::{self, BufRead};
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::{lite, parse_record_with, Dialect, Output, ParseError, Strictness};
+
+/// A single successfully parsed line along with where it came from.
+#[derive(Debug, PartialEq)]
+pub struct ParsedLine {
+    /// 1-based line number within the input
+    pub line_number: usize,
+    /// byte offset of the start of the line within the input
+    pub offset: u64,
+    /// the parsed record
+    pub output: Output,
+    /// problems tolerated because the reader is [lenient](Strictness::Lenient)
+    pub warnings: Vec<ParseError>,
+}
+
+/// Errors produced while reading records from an [`MmiReader`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReadError {
+    /// The underlying reader failed. No further items are produced after this.
+    Io(io::Error),
+    /// A line was not valid UTF-8.
+    InvalidUtf8 { line_number: usize, offset: u64 },
+    /// A line could not be parsed into a record.
+    Parse {
+        line_number: usize,
+        offset: u64,
+        line: String,
+        error: ParseError,
+    },
+}
+
+impl ReadError {
+    /// 1-based line number the error occurred on, if it is tied to a line.
+    pub fn line_number(&self) -> Option<usize> {
+        match self {
+            ReadError::Io(_) => None,
+            ReadError::InvalidUtf8 { line_number, .. } | ReadError::Parse { line_number, .. } => {
+                Some(*line_number)
+            }
+        }
+    }
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "could not read input: {}", e),
+            ReadError::InvalidUtf8 { line_number, .. } => {
+                write!(f, "line {} is not valid UTF-8", line_number)
+            }
+            ReadError::Parse {
+                line_number, error, ..
+            } => write!(f, "line {}: {}", line_number, error),
+        }
+    }
+}
+
+impl error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ReadError::Io(e) => Some(e),
+            ReadError::InvalidUtf8 { .. } => None,
+            ReadError::Parse { error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        ReadError::Io(e)
+    }
+}
+
+/// Iterator over the records of any [`BufRead`] containing fielded MMI output.
+///
+/// Each line is parsed with [`parse_record_with`], leniently unless changed with
+/// [`MmiReader::with_strictness`]. Output of MetaMap Lite is read by setting the
+/// [`Dialect`] with [`MmiReader::with_dialect`]. Lines that fail to parse are
+/// yielded as [`ReadError::Parse`] and reading continues with the next line,
+/// so one bad line never stops the whole stream.
+///
+/// Lines that are not records but are known to appear in MetaMap output are
+/// skipped without producing an item: blank lines, timestamp headers (such as
+/// `2022-05-03 10:15:22` or the output of `date`) and the banners MetaMap
+/// prints while running (`Processing 00000000.tx.1: ...`, `Berkeley DB
+/// databases ... are open.` and the like), as well as the input text echoed by
+/// `--sldi` (`24119710|Effects of heart attack`). Records have at least three
+/// `|`-separated fields, so these never hide a record; each skipped echo is
+/// still logged as a warning, as it may be a record cut short. More lines can be skipped with
+/// [`MmiReader::with_skip_patterns`], and [`MmiReader::skipped_lines`] tells
+/// how many were skipped so far.
+///
+/// ```rust
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let file = File::open("data/MMI_sample.txt").unwrap();
+/// for item in mmi_parser::MmiReader::new(BufReader::new(file)) {
+///     match item {
+///         Ok(parsed) => println!("{}: {:?}", parsed.line_number, parsed.output),
+///         Err(e) => eprintln!("{}", e),
+///     }
+/// }
+/// ```
+pub struct MmiReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    line_number: usize,
+    offset: u64,
+    finished: bool,
+    strictness: Strictness,
+    dialect: Dialect,
+    skip_patterns: Vec<Regex>,
+    skipped: usize,
+}
+
+impl<R: BufRead> MmiReader<R> {
+    /// Creates a new reader over `reader`.
+    pub fn new(reader: R) -> Self {
+        MmiReader {
+            reader,
+            buffer: Vec::new(),
+            line_number: 0,
+            offset: 0,
+            finished: false,
+            strictness: Strictness::Lenient,
+            dialect: Dialect::MetaMap,
+            skip_patterns: Vec::new(),
+            skipped: 0,
+        }
+    }
+
+    /// Sets how strictly record identifiers are validated.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Sets which program's fielded output the lines are parsed as.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Also skips the lines matching any of `patterns`, such as the input
+    /// echoed by some pipelines.
+    ///
+    /// ```rust
+    /// use regex::Regex;
+    ///
+    /// let input = "24119710|Some input text\n23074487|AA|FY|fiscal years|1|2|3|12|9362:2\n";
+    /// let mut reader = mmi_parser::MmiReader::new(input.as_bytes())
+    ///     .with_skip_patterns([Regex::new(r"^\d+\|[^|]*$").unwrap()]);
+    /// assert_eq!(reader.by_ref().count(), 1);
+    /// assert_eq!(reader.skipped_lines(), 1);
+    /// ```
+    pub fn with_skip_patterns(mut self, patterns: impl IntoIterator<Item = Regex>) -> Self {
+        self.skip_patterns.extend(patterns);
+        self
+    }
+
+    /// Number of lines skipped so far because they are not records.
+    pub fn skipped_lines(&self) -> usize {
+        self.skipped
+    }
+
+    /// Consumes the `MmiReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Timestamp headers and the banners MetaMap writes to its output.
+fn known_header() -> &'static Regex {
+    static HEADER: OnceLock<Regex> = OnceLock::new();
+    HEADER.get_or_init(|| {
+        Regex::new(concat!(
+            // ISO dates with a time, possibly after a label ("written 2022-05-03 10:15")
+            r"^\D{0,40}\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}",
+            // the output of `date` ("Tue May  3 10:15:22 EDT 2022")
+            r"|^(Mon|Tue|Wed|Thu|Fri|Sat|Sun) (Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec) +\d{1,2} \d{2}:\d{2}:\d{2}",
+            // program banners and the command line MetaMap was started with
+            r"|(?i)^(metamap|\S*/(SKRrun|metamap)\S*\s)",
+            r"|^(Processing \S+:|Berkeley DB databases|Static variants will come from|Derivational Variants:|Variant generation mode:|Control options:)",
+        ))
+        .expect("header patterns are valid")
+    })
+}
+
+/// Whether `line` is input text echoed by `--sldi` (`<id>|<input text>`): two
+/// fields, the second of which is neither empty nor (the start of) a record
+/// type, so that truncated records such as `123|MM` are still reported.
+fn is_input_echo(line: &str) -> bool {
+    match line.split_once('|') {
+        Some((id, text)) => {
+            let text = text.trim().to_ascii_uppercase();
+            !id.trim().is_empty()
+                && !text.is_empty()
+                && !text.contains('|')
+                && !["MMI", "AA", "UA"].iter().any(|t| t.starts_with(&text))
+        }
+        None => false,
+    }
+}
+
+/// Removes a trailing `\n` or `\r\n` from a line.
+fn trim_line_ending(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+impl<R: BufRead> Iterator for MmiReader<R> {
+    type Item = Result<ParsedLine, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // skipped lines are read in this loop, not by recursing
+        loop {
+            if self.finished {
+                return None;
+            }
+            self.buffer.clear();
+            let read = match self.reader.read_until(b'\n', &mut self.buffer) {
+                Ok(0) => {
+                    self.finished = true;
+                    return None;
+                }
+                Ok(read) => read,
+                Err(e) => {
+                    // avoid spinning forever on a reader that keeps failing
+                    self.finished = true;
+                    return Some(Err(ReadError::Io(e)));
+                }
+            };
+            self.line_number += 1;
+            let offset = self.offset;
+            self.offset += read as u64;
+
+            let line = match std::str::from_utf8(trim_line_ending(&self.buffer)) {
+                Ok(line) => line,
+                Err(_) => {
+                    return Some(Err(ReadError::InvalidUtf8 {
+                        line_number: self.line_number,
+                        offset,
+                    }))
+                }
+            };
+            let skip = if line.contains('|') {
+                let echo = is_input_echo(line);
+                if echo {
+                    log::warn!(
+                        "Skipping line {} as input text echoed by --sldi: {}",
+                        self.line_number,
+                        line
+                    );
+                }
+                echo
+            } else {
+                line.trim().is_empty() || known_header().is_match(line)
+            };
+            if skip || self.skip_patterns.iter().any(|p| p.is_match(line)) {
+                self.skipped += 1;
+                continue;
+            }
+            let parsed = match self.dialect {
+                Dialect::MetaMap => parse_record_with(line, self.strictness),
+                Dialect::Lite => lite::parse_record_with(line, self.strictness),
+            };
+            let item = match parsed {
+                Ok((output, warnings)) => Ok(ParsedLine {
+                    line_number: self.line_number,
+                    offset,
+                    output,
+                    warnings,
+                }),
+                Err(error) => Err(ReadError::Parse {
+                    line_number: self.line_number,
+                    offset,
+                    line: line.to_string(),
+                    error,
+                }),
+            };
+            return Some(item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_reader_line_numbers_and_offsets() {
+        let first = "23074487|AA|FY|fiscal years|1|2|3|12|9362:2";
+        let second = "24119710|MMI|170.37|Effect|C1280500|[qlco]|[\"effects\"-ti-1-\"Effects\"-noun-0]|TI|21/7|";
+        let input = format!("{}\r\n{}\n", first, second);
+        let items = MmiReader::new(Cursor::new(input))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].line_number, 1);
+        assert_eq!(items[0].offset, 0);
+        assert!(matches!(items[0].output, Output::AA(_)));
+        assert_eq!(items[1].line_number, 2);
+        assert_eq!(items[1].offset, first.len() as u64 + 2);
+        assert!(matches!(items[1].output, Output::MMI(_)));
+    }
+
+    #[test]
+    fn test_reader_continues_after_bad_lines() {
+        let input: &[u8] = b"not a record\n\xff\xfe\n23074487|AA|FY|fiscal years|1|2|3|12|9362:2";
+        let items = MmiReader::new(input).collect::<Vec<_>>();
+        assert_eq!(items.len(), 3);
+        match &items[0] {
+            Err(ReadError::Parse {
+                line_number,
+                offset,
+                line,
+                error,
+            }) => {
+                assert_eq!(*line_number, 1);
+                assert_eq!(*offset, 0);
+                assert_eq!(line, "not a record");
+                assert!(matches!(error, ParseError::FieldCount { .. }));
+            }
+            other => panic!("unexpected item {:?}", other),
+        }
+        assert!(matches!(
+            items[1],
+            Err(ReadError::InvalidUtf8 {
+                line_number: 2,
+                offset: 13
+            })
+        ));
+        assert_eq!(items[2].as_ref().unwrap().line_number, 3);
+    }
+
+    #[test]
+    fn test_reader_strictness() {
+        let input =
+            "23074487|AA|FY|fiscal years|1|2|3|12|9362:2\n|AA|FY|fiscal years|1|2|3|12|9362:2\n";
+        let strict = MmiReader::new(Cursor::new(input))
+            .with_strictness(Strictness::Strict)
+            .collect::<Vec<_>>();
+        assert!(strict[0].as_ref().unwrap().warnings.is_empty());
+        assert!(matches!(strict[1], Err(ReadError::Parse { .. })));
+
+        let lenient = MmiReader::new(Cursor::new(input))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(lenient[1].warnings.len(), 1);
+        assert_eq!(lenient[1].warnings[0].field(), Some("id"));
+    }
+
+    #[test]
+    fn test_reader_dialect() {
+        let input = "1|MMI|3.58|Name|C0027051|[dsyn]|[\"a\"-tx-1-\"a\"]|TX|0:1\n";
+        let metamap = MmiReader::new(Cursor::new(input)).collect::<Vec<_>>();
+        assert!(matches!(metamap[0], Err(ReadError::Parse { .. })));
+        let lite = MmiReader::new(Cursor::new(input))
+            .with_dialect(Dialect::Lite)
+            .collect::<Vec<_>>();
+        assert_eq!(lite[0].as_ref().unwrap().line_number, 1);
+    }
+
+    #[test]
+    fn test_reader_skips_headers() {
+        let record = "23074487|AA|FY|fiscal years|1|2|3|12|9362:2";
+        let input = [
+            "Tue May  3 10:15:22 EDT 2022",
+            "/opt/public_mm/bin/SKRrun.20 /opt/public_mm/bin/metamap20.BINARY.Linux -N",
+            "Berkeley DB databases (USAbase 2020AA strict model) are open.",
+            "Processing 00000000.tx.1: Fiscal years.",
+            "",
+            record,
+            "   ",
+            "written 2022-05-03 10:15:22",
+            record,
+            "not a record",
+            "24119710|Effects of heart attack",
+            "24119710|MMI",
+            "24119710|MM",
+            "24119710| mmi ",
+            "24119710|",
+        ]
+        .join("\n");
+        let mut reader = MmiReader::new(Cursor::new(input));
+        let items = reader.by_ref().collect::<Vec<_>>();
+        assert_eq!(reader.skipped_lines(), 8);
+        assert_eq!(items.len(), 7);
+        assert!(items[4..].iter().all(|item| item.is_err()));
+        assert_eq!(items[0].as_ref().unwrap().line_number, 6);
+        assert_eq!(items[1].as_ref().unwrap().line_number, 9);
+        assert!(matches!(
+            items[2],
+            Err(ReadError::Parse {
+                line_number: 10,
+                ..
+            })
+        ));
+        assert!(matches!(
+            items[3],
+            Err(ReadError::Parse {
+                line_number: 12,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_reader_skip_patterns() {
+        let input =
+            "# comment\n24119710|Some input text\n23074487|AA|FY|fiscal years|1|2|3|12|9362:2\n";
+        let items = MmiReader::new(Cursor::new(input)).collect::<Vec<_>>();
+        assert_eq!(items.iter().filter(|item| item.is_err()).count(), 1);
+
+        let patterns = [r"^#"].map(|p| Regex::new(p).unwrap());
+        let mut reader = MmiReader::new(Cursor::new(input)).with_skip_patterns(patterns);
+        let items = reader.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].line_number, 3);
+        assert_eq!(reader.skipped_lines(), 2);
+    }
+}
@@ -0,0 +1,287 @@
+//! Flattening of records into table rows, for CSV/TSV and other tabular output.
+//!
+//! MMI records hold lists (semantic types, triggers, positions, tree codes), so
+//! an [`Explode`] strategy decides whether a record becomes a single row with
+//! the lists joined into strings, or one row per trigger or per position.
+//! AA records are flat and always make a single row, with their own columns.
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::{AaOutput, MmiOutput, ValueError};
+
+/// How the nested parts of an MMI record are spread over rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Explode {
+    /// One row per record, with triggers and positions joined the way MMI
+    /// output writes them.
+    #[default]
+    Joined,
+    /// One row per trigger.
+    Triggers,
+    /// One row per span of positional information.
+    Positions,
+}
+
+impl FromStr for Explode {
+    type Err = ValueError;
+    /// Parses `joined`, `triggers` or `positions`, ignoring case.
+    fn from_str(s: &str) -> Result<Explode, ValueError> {
+        match s.to_lowercase().as_str() {
+            "joined" => Ok(Explode::Joined),
+            "triggers" => Ok(Explode::Triggers),
+            "positions" => Ok(Explode::Positions),
+            _ => Err(ValueError),
+        }
+    }
+}
+
+impl Display for Explode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Explode::Joined => "joined",
+            Explode::Triggers => "triggers",
+            Explode::Positions => "positions",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Columns every MMI row starts with.
+const MMI_COLUMNS: [&str; 7] = [
+    "id",
+    "score",
+    "name",
+    "cui",
+    "semantic_types",
+    "location",
+    "tree_codes",
+];
+
+/// Columns added by each [`Explode`] strategy.
+const JOINED_COLUMNS: [&str; 2] = ["triggers", "positional_info"];
+const TRIGGER_COLUMNS: [&str; 6] = [
+    "trigger_name",
+    "trigger_loc",
+    "trigger_loc_position",
+    "trigger_text",
+    "trigger_part_of_speech",
+    "trigger_negation",
+];
+const POSITION_COLUMNS: [&str; 5] = ["section", "occurrence", "start", "length", "case"];
+
+/// Columns of AA rows.
+pub const AA_COLUMNS: [&str; 10] = [
+    "id",
+    "abbreviation_type",
+    "short_form",
+    "long_form",
+    "short_token_count",
+    "short_character_count",
+    "long_token_count",
+    "long_character_count",
+    "start",
+    "length",
+];
+
+/// Header of the MMI rows produced with `explode`.
+pub fn mmi_header(explode: Explode) -> Vec<&'static str> {
+    let extra: &[&str] = match explode {
+        Explode::Joined => &JOINED_COLUMNS,
+        Explode::Triggers => &TRIGGER_COLUMNS,
+        Explode::Positions => &POSITION_COLUMNS,
+    };
+    MMI_COLUMNS.iter().chain(extra).copied().collect()
+}
+
+fn join<T: Display>(items: &[T], separator: &str) -> String {
+    items
+        .iter()
+        .map(T::to_string)
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Rows of an MMI record, matching [`mmi_header`] for the same `explode`.
+///
+/// Lists are joined with `;` (`,` for triggers, as in MMI output), and
+/// booleans are written as `true`/`false`. When exploding a record that has no
+/// triggers or positions, a single row with empty exploded columns is
+/// produced, so every record appears at least once.
+///
+/// ```rust
+/// use mmi_parser::rows::{mmi_header, mmi_rows, Explode};
+///
+/// let line = "24119710|MMI|637.30|Isopoda|C0598806|[euka]|[\"Isopod\"-ab-1-\"isopod\"-adj-0,\"Isopoda\"-ti-1-\"Isopoda\"-noun-0]|TI;AB|228/6;136/7|B01.050.500.131.365.400";
+/// let record = match mmi_parser::parse_record(line).unwrap() {
+///     mmi_parser::Output::MMI(record) => record,
+///     _ => unreachable!(),
+/// };
+/// let rows = mmi_rows(&record, Explode::Triggers);
+/// assert_eq!(rows.len(), 2);
+/// assert_eq!(rows[0].len(), mmi_header(Explode::Triggers).len());
+/// ```
+pub fn mmi_rows(record: &MmiOutput, explode: Explode) -> Vec<Vec<String>> {
+    let common = [
+        record.id.to_string(),
+        record.score.to_string(),
+        record.name.clone(),
+        record.cui.to_string(),
+        record.semantic_types.join(";"),
+        record.location.to_string(),
+        record
+            .tree_codes
+            .as_deref()
+            .map(|codes| join(codes, ";"))
+            .unwrap_or_default(),
+    ];
+    let row = |extra: Vec<String>| common.iter().cloned().chain(extra).collect::<Vec<_>>();
+    let rows: Vec<Vec<String>> = match explode {
+        Explode::Joined => vec![row(vec![
+            join(&record.triggers, ","),
+            record.positional_info.to_string(),
+        ])],
+        Explode::Triggers => record
+            .triggers
+            .iter()
+            .map(|trigger| {
+                row(vec![
+                    trigger.name.clone(),
+                    trigger.loc.to_string(),
+                    trigger.loc_position.to_string(),
+                    trigger.text.clone(),
+                    trigger.part_of_speech.clone(),
+                    trigger.negation.to_string(),
+                ])
+            })
+            .collect(),
+        Explode::Positions => {
+            let locations = record.occurrence_locations();
+            record
+                .positional_info
+                .occurrences
+                .iter()
+                .zip(locations)
+                .enumerate()
+                .flat_map(|(i, (occurrence, location))| {
+                    let section = location.map(ToString::to_string).unwrap_or_default();
+                    occurrence
+                        .spans
+                        .iter()
+                        .map(move |span| (section.clone(), i, span.start, span.length))
+                })
+                .map(|(section, occurrence, start, length)| {
+                    row(vec![
+                        section,
+                        occurrence.to_string(),
+                        start.to_string(),
+                        length.to_string(),
+                        format!("{:?}", record.positional_info.case),
+                    ])
+                })
+                .collect()
+        }
+    };
+    if rows.is_empty() {
+        let width = mmi_header(explode).len() - common.len();
+        vec![row(vec![String::new(); width])]
+    } else {
+        rows
+    }
+}
+
+/// The row of an AA record, matching [`AA_COLUMNS`].
+pub fn aa_row(record: &AaOutput) -> Vec<String> {
+    vec![
+        record.id.to_string(),
+        record.abbreviation_type.to_string(),
+        record.short_form.clone(),
+        record.long_form.clone(),
+        record.short_token_count.to_string(),
+        record.short_character_count.to_string(),
+        record.long_token_count.to_string(),
+        record.long_character_count.to_string(),
+        record.positional_info.start.to_string(),
+        record.positional_info.length.to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_record, Output};
+
+    fn mmi(line: &str) -> MmiOutput {
+        match parse_record(line).unwrap() {
+            Output::MMI(record) => record,
+            other => panic!("expected an MMI record, got {:?}", other),
+        }
+    }
+
+    const LINE: &str = "24119710|MMI|637.30|Isopoda|C0598806|[euka,anim]|[\"Isopod\"-ab-1-\"isopod\"-adj-0,\"Isopoda\"-ti-1-\"Isopoda\"-noun-0]|TI;AB|228/6;136/7|B01.050.500.131.365.400;B01";
+
+    #[test]
+    fn test_mmi_rows_joined() {
+        let rows = mmi_rows(&mmi(LINE), Explode::Joined);
+        assert_eq!(
+            rows,
+            [[
+                "24119710",
+                "637.3",
+                "Isopoda",
+                "C0598806",
+                "euka;anim",
+                "TI;AB",
+                "B01.050.500.131.365.400;B01",
+                "\"Isopod\"-ab-1-\"isopod\"-adj-0,\"Isopoda\"-ti-1-\"Isopoda\"-noun-0",
+                "228/6;136/7",
+            ]]
+        );
+        assert_eq!(mmi_header(Explode::Joined).len(), rows[0].len());
+    }
+
+    #[test]
+    fn test_mmi_rows_exploded() {
+        let record = mmi(LINE);
+        let rows = mmi_rows(&record, Explode::Triggers);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[1][7..],
+            ["Isopoda", "TI", "1", "Isopoda", "noun", "false"]
+        );
+        let rows = mmi_rows(&record, Explode::Positions);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][7..], ["AB", "0", "228", "6", "A"]);
+        assert_eq!(rows[1][7..], ["TI", "1", "136", "7", "A"]);
+        assert_eq!(mmi_header(Explode::Positions).len(), rows[0].len());
+    }
+
+    #[test]
+    fn test_mmi_rows_without_positions() {
+        let mut record = mmi(LINE);
+        record.positional_info.occurrences.clear();
+        let rows = mmi_rows(&record, Explode::Positions);
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0][7..].iter().all(String::is_empty));
+    }
+
+    #[test]
+    fn test_aa_row() {
+        match parse_record("23074487|AA|FY|fiscal years|1|2|3|12|9362:2").unwrap() {
+            Output::AA(aa) => {
+                let row = aa_row(&aa);
+                assert_eq!(row.len(), AA_COLUMNS.len());
+                assert_eq!(row[3], "fiscal years");
+                assert_eq!(row[8..], ["9362", "2"]);
+            }
+            other => panic!("expected an AA record, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_explode_from_str() {
+        assert_eq!("Triggers".parse::<Explode>().unwrap(), Explode::Triggers);
+        assert_eq!(Explode::Positions.to_string(), "positions");
+        assert!("rows".parse::<Explode>().is_err());
+    }
+}
@@ -0,0 +1,336 @@
+//! The UMLS semantic types that appear in the `semantic_types` field of MMI output.
+//!
+//! MetaMap only writes the four letter abbreviations (`euka`, `qlco`, ...).
+//! [`SemanticType`] maps them to their TUI, full name and
+//! [semantic group](https://lhncbc.nlm.nih.gov/ii/tools/MetaMap/documentation/SemanticTypesAndGroups.html)
+//! using a table compiled into the crate.
+
+use serde::Serialize;
+use std::fmt::{self, Display};
+
+/// UMLS release the embedded table follows.
+///
+/// Six chemical types retired from the Semantic Network since (`carb`, `eico`,
+/// `lipd`, `nsba`, `opco` and `strd`) are kept because MetaMap 2016 data still emits them.
+pub const SEMANTIC_TYPES_VERSION: &str = "2018AB";
+
+/// The semantic groups semantic types are partitioned into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub enum SemanticGroup {
+    ACTI,
+    ANAT,
+    CHEM,
+    CONC,
+    DEVI,
+    DISO,
+    GENE,
+    GEOG,
+    LIVB,
+    OBJC,
+    OCCU,
+    ORGA,
+    PHEN,
+    PHYS,
+    PROC,
+}
+
+impl SemanticGroup {
+    /// Full name of the group, e.g. `Chemicals & Drugs` for [`SemanticGroup::CHEM`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            SemanticGroup::ACTI => "Activities & Behaviors",
+            SemanticGroup::ANAT => "Anatomy",
+            SemanticGroup::CHEM => "Chemicals & Drugs",
+            SemanticGroup::CONC => "Concepts & Ideas",
+            SemanticGroup::DEVI => "Devices",
+            SemanticGroup::DISO => "Disorders",
+            SemanticGroup::GENE => "Genes & Molecular Sequences",
+            SemanticGroup::GEOG => "Geographic Areas",
+            SemanticGroup::LIVB => "Living Beings",
+            SemanticGroup::OBJC => "Objects",
+            SemanticGroup::OCCU => "Occupations",
+            SemanticGroup::ORGA => "Organizations",
+            SemanticGroup::PHEN => "Phenomena",
+            SemanticGroup::PHYS => "Physiology",
+            SemanticGroup::PROC => "Procedures",
+        }
+    }
+}
+
+impl Display for SemanticGroup {
+    /// Writes the four letter group code.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// A UMLS semantic type.
+///
+/// ```rust
+/// use mmi_parser::{SemanticGroup, SemanticType};
+///
+/// let euka = SemanticType::from_abbreviation("euka").unwrap();
+/// assert_eq!(euka.tui, "T204");
+/// assert_eq!(euka.name, "Eukaryote");
+/// assert_eq!(euka.group, SemanticGroup::LIVB);
+/// assert_eq!(SemanticType::from_tui("T204"), Some(euka));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct SemanticType {
+    /// four letter abbreviation used by MetaMap
+    pub abbreviation: &'static str,
+    /// type unique identifier, e.g. `T204`
+    pub tui: &'static str,
+    /// full name, e.g. `Eukaryote`
+    pub name: &'static str,
+    /// semantic group the type belongs to
+    pub group: SemanticGroup,
+}
+
+impl SemanticType {
+    /// Looks up a semantic type by its abbreviation, ignoring case.
+    pub fn from_abbreviation(abbreviation: &str) -> Option<&'static SemanticType> {
+        SEMANTIC_TYPES
+            .iter()
+            .find(|st| st.abbreviation.eq_ignore_ascii_case(abbreviation))
+    }
+
+    /// Looks up a semantic type by its TUI, ignoring case.
+    pub fn from_tui(tui: &str) -> Option<&'static SemanticType> {
+        SEMANTIC_TYPES
+            .iter()
+            .find(|st| st.tui.eq_ignore_ascii_case(tui))
+    }
+
+    /// Looks up a semantic type by its full name (as MetaMap's human-readable
+    /// output writes it), ignoring case.
+    pub fn from_name(name: &str) -> Option<&'static SemanticType> {
+        SEMANTIC_TYPES
+            .iter()
+            .find(|st| st.name.eq_ignore_ascii_case(name))
+    }
+
+    /// All known semantic types, sorted by abbreviation.
+    pub fn all() -> &'static [SemanticType] {
+        SEMANTIC_TYPES
+    }
+}
+
+impl Display for SemanticType {
+    /// Writes the abbreviation, as it appears in MMI records.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.abbreviation)
+    }
+}
+
+const fn st(
+    abbreviation: &'static str,
+    tui: &'static str,
+    name: &'static str,
+    group: SemanticGroup,
+) -> SemanticType {
+    SemanticType {
+        abbreviation,
+        tui,
+        name,
+        group,
+    }
+}
+
+use SemanticGroup::*;
+
+/// abbreviation|TUI|name as in the UMLS `SemanticTypes` file, group as in `SemGroups`.
+const SEMANTIC_TYPES: &[SemanticType] = &[
+    st("aapp", "T116", "Amino Acid, Peptide, or Protein", CHEM),
+    st("acab", "T020", "Acquired Abnormality", DISO),
+    st("acty", "T052", "Activity", ACTI),
+    st("aggp", "T100", "Age Group", LIVB),
+    st("amas", "T087", "Amino Acid Sequence", GENE),
+    st("amph", "T011", "Amphibian", LIVB),
+    st("anab", "T190", "Anatomical Abnormality", DISO),
+    st("anim", "T008", "Animal", LIVB),
+    st("anst", "T017", "Anatomical Structure", ANAT),
+    st("antb", "T195", "Antibiotic", CHEM),
+    st("arch", "T194", "Archaeon", LIVB),
+    st("bacs", "T123", "Biologically Active Substance", CHEM),
+    st("bact", "T007", "Bacterium", LIVB),
+    st("bdsu", "T031", "Body Substance", ANAT),
+    st("bdsy", "T022", "Body System", ANAT),
+    st("bhvr", "T053", "Behavior", ACTI),
+    st("biof", "T038", "Biologic Function", PHYS),
+    st("bird", "T012", "Bird", LIVB),
+    st("blor", "T029", "Body Location or Region", ANAT),
+    st("bmod", "T091", "Biomedical Occupation or Discipline", OCCU),
+    st("bodm", "T122", "Biomedical or Dental Material", CHEM),
+    st("bpoc", "T023", "Body Part, Organ, or Organ Component", ANAT),
+    st("bsoj", "T030", "Body Space or Junction", ANAT),
+    st("carb", "T118", "Carbohydrate", CHEM),
+    st("celc", "T026", "Cell Component", ANAT),
+    st("celf", "T043", "Cell Function", PHYS),
+    st("cell", "T025", "Cell", ANAT),
+    st("cgab", "T019", "Congenital Abnormality", DISO),
+    st("chem", "T103", "Chemical", CHEM),
+    st("chvf", "T120", "Chemical Viewed Functionally", CHEM),
+    st("chvs", "T104", "Chemical Viewed Structurally", CHEM),
+    st("clas", "T185", "Classification", CONC),
+    st("clna", "T201", "Clinical Attribute", PHYS),
+    st("clnd", "T200", "Clinical Drug", CHEM),
+    st("cnce", "T077", "Conceptual Entity", CONC),
+    st("comd", "T049", "Cell or Molecular Dysfunction", DISO),
+    st("crbs", "T088", "Carbohydrate Sequence", GENE),
+    st("diap", "T060", "Diagnostic Procedure", PROC),
+    st("dora", "T056", "Daily or Recreational Activity", ACTI),
+    st("drdd", "T203", "Drug Delivery Device", DEVI),
+    st("dsyn", "T047", "Disease or Syndrome", DISO),
+    st("edac", "T065", "Educational Activity", PROC),
+    st("eehu", "T069", "Environmental Effect of Humans", PHEN),
+    st("eico", "T111", "Eicosanoid", CHEM),
+    st("elii", "T196", "Element, Ion, or Isotope", CHEM),
+    st("emod", "T050", "Experimental Model of Disease", DISO),
+    st("emst", "T018", "Embryonic Structure", ANAT),
+    st("enty", "T071", "Entity", OBJC),
+    st("enzy", "T126", "Enzyme", CHEM),
+    st("euka", "T204", "Eukaryote", LIVB),
+    st("evnt", "T051", "Event", ACTI),
+    st("famg", "T099", "Family Group", LIVB),
+    st("ffas", "T021", "Fully Formed Anatomical Structure", ANAT),
+    st("fish", "T013", "Fish", LIVB),
+    st("fndg", "T033", "Finding", DISO),
+    st("fngs", "T004", "Fungus", LIVB),
+    st("food", "T168", "Food", OBJC),
+    st("ftcn", "T169", "Functional Concept", CONC),
+    st("genf", "T045", "Genetic Function", PHYS),
+    st("geoa", "T083", "Geographic Area", GEOG),
+    st("gngm", "T028", "Gene or Genome", GENE),
+    st("gora", "T064", "Governmental or Regulatory Activity", ACTI),
+    st("grpa", "T102", "Group Attribute", CONC),
+    st("grup", "T096", "Group", LIVB),
+    st("hcpp", "T068", "Human-caused Phenomenon or Process", PHEN),
+    st("hcro", "T093", "Health Care Related Organization", ORGA),
+    st("hlca", "T058", "Health Care Activity", PROC),
+    st("hops", "T131", "Hazardous or Poisonous Substance", CHEM),
+    st("horm", "T125", "Hormone", CHEM),
+    st("humn", "T016", "Human", LIVB),
+    st("idcn", "T078", "Idea or Concept", CONC),
+    st("imft", "T129", "Immunologic Factor", CHEM),
+    st("inbe", "T055", "Individual Behavior", ACTI),
+    st("inch", "T197", "Inorganic Chemical", CHEM),
+    st("inpo", "T037", "Injury or Poisoning", DISO),
+    st("inpr", "T170", "Intellectual Product", CONC),
+    st(
+        "irda",
+        "T130",
+        "Indicator, Reagent, or Diagnostic Aid",
+        CHEM,
+    ),
+    st("lang", "T171", "Language", CONC),
+    st("lbpr", "T059", "Laboratory Procedure", PROC),
+    st("lbtr", "T034", "Laboratory or Test Result", PHEN),
+    st("lipd", "T119", "Lipid", CHEM),
+    st("mamm", "T015", "Mammal", LIVB),
+    st("mbrt", "T063", "Molecular Biology Research Technique", PROC),
+    st("mcha", "T066", "Machine Activity", ACTI),
+    st("medd", "T074", "Medical Device", DEVI),
+    st("menp", "T041", "Mental Process", PHYS),
+    st("mnob", "T073", "Manufactured Object", OBJC),
+    st("mobd", "T048", "Mental or Behavioral Dysfunction", DISO),
+    st("moft", "T044", "Molecular Function", PHYS),
+    st("mosq", "T085", "Molecular Sequence", GENE),
+    st("neop", "T191", "Neoplastic Process", DISO),
+    st(
+        "nnon",
+        "T114",
+        "Nucleic Acid, Nucleoside, or Nucleotide",
+        CHEM,
+    ),
+    st("npop", "T070", "Natural Phenomenon or Process", PHEN),
+    st(
+        "nsba",
+        "T124",
+        "Neuroreactive Substance or Biogenic Amine",
+        CHEM,
+    ),
+    st("nusq", "T086", "Nucleotide Sequence", GENE),
+    st("ocac", "T057", "Occupational Activity", ACTI),
+    st("ocdi", "T090", "Occupation or Discipline", OCCU),
+    st("opco", "T115", "Organophosphorus Compound", CHEM),
+    st("orch", "T109", "Organic Chemical", CHEM),
+    st("orga", "T032", "Organism Attribute", PHYS),
+    st("orgf", "T040", "Organism Function", PHYS),
+    st("orgm", "T001", "Organism", LIVB),
+    st("orgt", "T092", "Organization", ORGA),
+    st("ortf", "T042", "Organ or Tissue Function", PHYS),
+    st("patf", "T046", "Pathologic Function", DISO),
+    st("phob", "T072", "Physical Object", OBJC),
+    st("phpr", "T067", "Phenomenon or Process", PHEN),
+    st("phsf", "T039", "Physiologic Function", PHYS),
+    st("phsu", "T121", "Pharmacologic Substance", CHEM),
+    st("plnt", "T002", "Plant", LIVB),
+    st("podg", "T101", "Patient or Disabled Group", LIVB),
+    st("popg", "T098", "Population Group", LIVB),
+    st("prog", "T097", "Professional or Occupational Group", LIVB),
+    st("pros", "T094", "Professional Society", ORGA),
+    st("qlco", "T080", "Qualitative Concept", CONC),
+    st("qnco", "T081", "Quantitative Concept", CONC),
+    st("rcpt", "T192", "Receptor", CHEM),
+    st("rept", "T014", "Reptile", LIVB),
+    st("resa", "T062", "Research Activity", PROC),
+    st("resd", "T075", "Research Device", DEVI),
+    st("rnlw", "T089", "Regulation or Law", CONC),
+    st("sbst", "T167", "Substance", OBJC),
+    st("shro", "T095", "Self-help or Relief Organization", ORGA),
+    st("socb", "T054", "Social Behavior", ACTI),
+    st("sosy", "T184", "Sign or Symptom", DISO),
+    st("spco", "T082", "Spatial Concept", CONC),
+    st("strd", "T110", "Steroid", CHEM),
+    st("tisu", "T024", "Tissue", ANAT),
+    st("tmco", "T079", "Temporal Concept", CONC),
+    st("topp", "T061", "Therapeutic or Preventive Procedure", PROC),
+    st("virs", "T005", "Virus", LIVB),
+    st("vita", "T127", "Vitamin", CHEM),
+    st("vtbt", "T010", "Vertebrate", LIVB),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_semantic_type_lookup() {
+        let qlco = SemanticType::from_abbreviation("QLCO").unwrap();
+        assert_eq!(qlco.tui, "T080");
+        assert_eq!(qlco.name, "Qualitative Concept");
+        assert_eq!(qlco.group, SemanticGroup::CONC);
+        assert_eq!(qlco.group.name(), "Concepts & Ideas");
+        assert_eq!(SemanticType::from_tui("t080"), Some(qlco));
+        assert_eq!(SemanticType::from_name("qualitative concept"), Some(qlco));
+        assert_eq!(SemanticType::from_abbreviation("zzzz"), None);
+        assert_eq!(SemanticType::from_tui("T999"), None);
+    }
+
+    #[test]
+    fn test_semantic_type_table() {
+        let all = SemanticType::all();
+        assert_eq!(all.len(), 133);
+        for pair in all.windows(2) {
+            assert!(pair[0].abbreviation < pair[1].abbreviation);
+        }
+        for st in all {
+            assert_eq!(SemanticType::from_tui(st.tui), Some(st));
+        }
+    }
+
+    #[test]
+    fn test_semantic_type_details() {
+        let s1 = "24119710|MMI|637.30|Isopoda|C0598806|[euka,xxxx]|[\"Isopod\"-ab-1-\"isopod\"-adj-0]|AB|136/7|";
+        match crate::parse_record(s1).unwrap() {
+            crate::Output::MMI(mmi) => {
+                let details = mmi.semantic_type_details();
+                assert_eq!(details.len(), 2);
+                assert_eq!(details[0].unwrap().tui, "T204");
+                assert_eq!(details[1], None);
+            }
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! Export of parsed records into a normalized SQLite database, available with
+//! the `sqlite` feature.
+//!
+//! The lists inside MMI records get tables of their own, linked to their
+//! record by `concept_id` (see [`SCHEMA`] for the full definition):
+//!
+//! * `documents`: one row per document `id`;
+//! * `concepts`: the scalar fields of [`MmiOutput`], one row per MMI record,
+//!   with a `NULL` `cui` for the [`Cui::missing`](crate::Cui::missing) placeholder;
+//! * `semantic_types`, `triggers`, `positions` and `tree_codes`: the items of
+//!   each record's lists, with their `position` in the list;
+//! * `abbreviations`: the fields of [`AaOutput`], one row per AA record.
+//!
+//! Tables and indexes are only created if they do not exist yet, so records of
+//! several runs can be appended to the same database. Appending the same input
+//! twice adds its records twice.
+
+use std::path::Path;
+
+use rusqlite::{params, Connection, Result};
+
+use crate::{AaOutput, MmiOutput, Output};
+
+/// Tables and indexes created by [`SqliteWriter`].
+pub const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS documents (
+    id TEXT PRIMARY KEY
+);
+CREATE TABLE IF NOT EXISTS concepts (
+    concept_id INTEGER PRIMARY KEY,
+    id TEXT NOT NULL REFERENCES documents (id),
+    mmi TEXT NOT NULL,
+    score REAL NOT NULL,
+    name TEXT NOT NULL,
+    cui TEXT,
+    location TEXT NOT NULL,
+    positional_info_case TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS semantic_types (
+    concept_id INTEGER NOT NULL REFERENCES concepts (concept_id),
+    position INTEGER NOT NULL,
+    semantic_type TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS triggers (
+    concept_id INTEGER NOT NULL REFERENCES concepts (concept_id),
+    position INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    loc TEXT NOT NULL,
+    loc_position INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    part_of_speech TEXT NOT NULL,
+    negation INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS positions (
+    concept_id INTEGER NOT NULL REFERENCES concepts (concept_id),
+    occurrence INTEGER NOT NULL,
+    section TEXT NOT NULL,
+    span INTEGER NOT NULL,
+    start INTEGER NOT NULL,
+    length INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tree_codes (
+    concept_id INTEGER NOT NULL REFERENCES concepts (concept_id),
+    position INTEGER NOT NULL,
+    tree_code TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS abbreviations (
+    abbreviation_id INTEGER PRIMARY KEY,
+    id TEXT NOT NULL REFERENCES documents (id),
+    abbreviation_type TEXT NOT NULL,
+    short_form TEXT NOT NULL,
+    long_form TEXT NOT NULL,
+    short_token_count INTEGER NOT NULL,
+    short_character_count INTEGER NOT NULL,
+    long_token_count INTEGER NOT NULL,
+    long_character_count INTEGER NOT NULL,
+    start INTEGER NOT NULL,
+    length INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS concepts_id ON concepts (id);
+CREATE INDEX IF NOT EXISTS concepts_cui ON concepts (cui);
+CREATE INDEX IF NOT EXISTS semantic_types_concept_id ON semantic_types (concept_id);
+CREATE INDEX IF NOT EXISTS triggers_concept_id ON triggers (concept_id);
+CREATE INDEX IF NOT EXISTS positions_concept_id ON positions (concept_id);
+CREATE INDEX IF NOT EXISTS tree_codes_concept_id ON tree_codes (concept_id);
+CREATE INDEX IF NOT EXISTS abbreviations_id ON abbreviations (id);
+";
+
+/// Number of records written per transaction.
+const TRANSACTION_SIZE: usize = 10_000;
+
+/// Writes records into a database with the tables of [`SCHEMA`].
+///
+/// Records are written in transactions of many records each, so
+/// [`finish`](SqliteWriter::finish) must be called to commit the last ones.
+///
+/// ```rust
+/// use mmi_parser::sqlite::SqliteWriter;
+///
+/// let connection = rusqlite::Connection::open_in_memory().unwrap();
+/// let mut writer = SqliteWriter::new(connection).unwrap();
+/// let record = mmi_parser::parse_record("23074487|AA|FY|fiscal years|1|2|3|12|9362:2").unwrap();
+/// writer.write(&record).unwrap();
+/// let connection = writer.finish().unwrap();
+/// let count: i64 = connection
+///     .query_row("SELECT count(*) FROM abbreviations", [], |row| row.get(0))
+///     .unwrap();
+/// assert_eq!(count, 1);
+/// ```
+pub struct SqliteWriter {
+    connection: Connection,
+    pending: usize,
+}
+
+impl SqliteWriter {
+    /// Opens (or creates) the database at `path`, adding any missing tables.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(Connection::open(path)?)
+    }
+
+    /// Writes into the database of `connection`, adding any missing tables.
+    pub fn new(connection: Connection) -> Result<Self> {
+        connection.execute_batch("PRAGMA foreign_keys = ON;")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(SqliteWriter {
+            connection,
+            pending: 0,
+        })
+    }
+
+    /// Adds a record, along with its document if that is not in the database yet.
+    pub fn write(&mut self, output: &Output) -> Result<()> {
+        if self.connection.is_autocommit() {
+            self.connection.execute_batch("BEGIN")?;
+        }
+        match output {
+            Output::MMI(record) => self.insert_mmi(record)?,
+            Output::AA(record) => self.insert_aa(record)?,
+        }
+        self.pending += 1;
+        if self.pending >= TRANSACTION_SIZE {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Commits the records written so far and returns the connection.
+    pub fn finish(mut self) -> Result<Connection> {
+        self.commit()?;
+        Ok(self.connection)
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        if !self.connection.is_autocommit() {
+            self.connection.execute_batch("COMMIT")?;
+        }
+        self.pending = 0;
+        Ok(())
+    }
+
+    fn insert_document(&self, id: &str) -> Result<()> {
+        self.connection
+            .prepare_cached("INSERT OR IGNORE INTO documents (id) VALUES (?1)")?
+            .execute([id])?;
+        Ok(())
+    }
+
+    fn insert_mmi(&self, record: &MmiOutput) -> Result<()> {
+        self.insert_document(record.id.as_str())?;
+        self.connection
+            .prepare_cached(
+                "INSERT INTO concepts (id, mmi, score, name, cui, location, positional_info_case)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?
+            .execute(params![
+                record.id.as_str(),
+                record.mmi,
+                record.score,
+                record.name,
+                Some(record.cui.as_str()).filter(|_| !record.cui.is_missing()),
+                record.location.to_string(),
+                format!("{:?}", record.positional_info.case),
+            ])?;
+        let concept = self.connection.last_insert_rowid();
+
+        let mut statement = self.connection.prepare_cached(
+            "INSERT INTO semantic_types (concept_id, position, semantic_type) VALUES (?1, ?2, ?3)",
+        )?;
+        for (i, semantic_type) in record.semantic_types.iter().enumerate() {
+            statement.execute(params![concept, i, semantic_type])?;
+        }
+
+        let mut statement = self.connection.prepare_cached(
+            "INSERT INTO triggers (concept_id, position, name, loc, loc_position, text, part_of_speech, negation)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )?;
+        for (i, trigger) in record.triggers.iter().enumerate() {
+            statement.execute(params![
+                concept,
+                i,
+                trigger.name,
+                trigger.loc.to_string(),
+                trigger.loc_position,
+                trigger.text,
+                trigger.part_of_speech,
+                trigger.negation,
+            ])?;
+        }
+
+        let mut statement = self.connection.prepare_cached(
+            "INSERT INTO positions (concept_id, occurrence, section, span, start, length)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        let locations = record.occurrence_locations();
+        let occurrences = record.positional_info.occurrences.iter().zip(locations);
+        for (i, (occurrence, location)) in occurrences.enumerate() {
+            let section = location.map(ToString::to_string).unwrap_or_default();
+            for (j, span) in occurrence.spans.iter().enumerate() {
+                statement.execute(params![concept, i, section, j, span.start, span.length])?;
+            }
+        }
+
+        let mut statement = self.connection.prepare_cached(
+            "INSERT INTO tree_codes (concept_id, position, tree_code) VALUES (?1, ?2, ?3)",
+        )?;
+        for (i, code) in record.tree_codes.iter().flatten().enumerate() {
+            statement.execute(params![concept, i, code.as_str()])?;
+        }
+        Ok(())
+    }
+
+    fn insert_aa(&self, record: &AaOutput) -> Result<()> {
+        self.insert_document(record.id.as_str())?;
+        self.connection
+            .prepare_cached(
+                "INSERT INTO abbreviations (id, abbreviation_type, short_form, long_form,
+                     short_token_count, short_character_count, long_token_count,
+                     long_character_count, start, length)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?
+            .execute(params![
+                record.id.as_str(),
+                record.abbreviation_type.to_string(),
+                record.short_form,
+                record.long_form,
+                record.short_token_count,
+                record.short_character_count,
+                record.long_token_count,
+                record.long_character_count,
+                record.positional_info.start,
+                record.positional_info.length,
+            ])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_record;
+
+    const LINES: [&str; 3] = [
+        "24119710|MMI|637.30|Isopoda|C0598806|[euka]|[\"Isopod\"-ab-1-\"isopod\"-adj-0,\"Isopoda\"-ti-1-\"Isopoda\"-noun-0]|TI;AB|228/6;136/7|B01.050.500.131.365.400",
+        "24119710|MMI|5.18|Heart|C0018787|[bpoc]|[\"heart\"-ab-1-\"heart\"-noun-1]|AB|[11/5],[40/5,50/3]|",
+        "23074487|AA|FY|fiscal years|1|2|3|12|9362:2",
+    ];
+
+    fn count(connection: &Connection, table: &str) -> i64 {
+        connection
+            .query_row(&format!("SELECT count(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    fn write_all(connection: Connection) -> Connection {
+        let mut writer = SqliteWriter::new(connection).unwrap();
+        for line in LINES {
+            writer.write(&parse_record(line).unwrap()).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_sqlite_writer() {
+        let connection = write_all(Connection::open_in_memory().unwrap());
+        assert_eq!(count(&connection, "documents"), 2);
+        assert_eq!(count(&connection, "concepts"), 2);
+        assert_eq!(count(&connection, "semantic_types"), 2);
+        assert_eq!(count(&connection, "triggers"), 3);
+        assert_eq!(count(&connection, "positions"), 5);
+        assert_eq!(count(&connection, "tree_codes"), 1);
+        assert_eq!(count(&connection, "abbreviations"), 1);
+        let (section, start): (String, i32) = connection
+            .query_row(
+                "SELECT section, start FROM positions JOIN concepts USING (concept_id)
+                 WHERE cui = 'C0598806' AND occurrence = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((section.as_str(), start), ("TI", 136));
+        let negated: bool = connection
+            .query_row(
+                "SELECT negation FROM triggers JOIN concepts USING (concept_id) WHERE cui = 'C0018787'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(negated);
+    }
+
+    #[test]
+    fn test_sqlite_missing_cui() {
+        let line = "1|MMI|861.00|Heart||[bpoc]|[\"Heart\"-ti-1-\"of heart\"--0]|TI||";
+        let (record, _) = crate::parse_record_with(line, crate::Strictness::Lenient).unwrap();
+        let mut writer = SqliteWriter::new(Connection::open_in_memory().unwrap()).unwrap();
+        writer.write(&record).unwrap();
+        let connection = writer.finish().unwrap();
+        let cui: Option<String> = connection
+            .query_row("SELECT cui FROM concepts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(cui, None);
+    }
+
+    #[test]
+    fn test_sqlite_appends() {
+        let connection = write_all(Connection::open_in_memory().unwrap());
+        let connection = write_all(connection);
+        assert_eq!(count(&connection, "documents"), 2);
+        assert_eq!(count(&connection, "concepts"), 4);
+        assert_eq!(count(&connection, "abbreviations"), 2);
+    }
+}
@@ -0,0 +1,219 @@
+//! MeSH tree codes as found in the last field of MMI records.
+//!
+//! A [tree number](https://www.nlm.nih.gov/mesh/intro_trees.html) such as
+//! `B01.050.500` places a descriptor in the MeSH hierarchy: the letter is the
+//! top-level category (`B` = Organisms), and every `.`-separated segment goes one
+//! level deeper.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::ValueError;
+
+/// A single MeSH tree code, e.g. `B01.050.500.131.365.400`.
+///
+/// Codes are ordered segment by segment, so a code sorts directly after its
+/// parent and before its parent's next sibling.
+///
+/// Deserialization accepts any string so that records parsed leniently can be
+/// read back; use [`TreeCode::is_valid`] to check.
+///
+/// ```rust
+/// use mmi_parser::TreeCode;
+///
+/// let code: TreeCode = "B01.050.500".parse().unwrap();
+/// assert_eq!(code.category(), 'B');
+/// assert_eq!(code.depth(), 3);
+/// assert_eq!(code.parent().unwrap().as_str(), "B01.050");
+/// assert!(code.is_descendant_of(&"B01".parse().unwrap()));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TreeCode(String);
+
+impl TreeCode {
+    /// Checks that `code` is a letter followed by digits, then any number of
+    /// `.`-separated groups of digits.
+    pub(crate) fn is_valid_str(code: &str) -> bool {
+        let mut segments = code.split('.');
+        let first = segments.next().unwrap_or_default();
+        let mut chars = first.chars();
+        let letter = matches!(chars.next(), Some(c) if c.is_ascii_uppercase());
+        let number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        letter && number(chars.as_str()) && segments.all(number)
+    }
+
+    /// Wraps a value without validating it, used by lenient parsing.
+    pub(crate) fn new_unchecked(code: String) -> TreeCode {
+        TreeCode(code)
+    }
+
+    /// Whether the code is a letter followed by digits, then any number of
+    /// `.`-separated groups of digits.
+    pub fn is_valid(&self) -> bool {
+        TreeCode::is_valid_str(&self.0)
+    }
+
+    /// The code as written in MMI output.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The top-level MeSH category letter, e.g. `B` for `B01.050` (`'\0'` for
+    /// an empty code kept by lenient parsing).
+    pub fn category(&self) -> char {
+        self.0.chars().next().unwrap_or_default()
+    }
+
+    /// The `.`-separated segments of the code, e.g. `B01`, `050`.
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.0.split('.')
+    }
+
+    /// Number of levels below the category, `B01` being depth 1.
+    pub fn depth(&self) -> usize {
+        self.segments().count()
+    }
+
+    /// The code one level up, or `None` for a top-level code like `B01`.
+    pub fn parent(&self) -> Option<TreeCode> {
+        self.0
+            .rfind('.')
+            .map(|end| TreeCode(self.0[..end].to_string()))
+    }
+
+    /// Every code above this one, starting from the top-level code.
+    pub fn ancestors(&self) -> Vec<TreeCode> {
+        self.0
+            .match_indices('.')
+            .map(|(end, _)| TreeCode(self.0[..end].to_string()))
+            .collect()
+    }
+
+    /// The top-level code this code falls under, e.g. `B01` for `B01.050.500`.
+    pub fn root(&self) -> TreeCode {
+        TreeCode(self.segments().next().unwrap_or_default().to_string())
+    }
+
+    /// Whether this code lies strictly below `other` in the hierarchy.
+    pub fn is_descendant_of(&self, other: &TreeCode) -> bool {
+        self.0.len() > other.0.len()
+            && self.0.starts_with(&other.0)
+            && self.0.as_bytes()[other.0.len()] == b'.'
+    }
+}
+
+impl FromStr for TreeCode {
+    type Err = ValueError;
+    /// Parses a tree code, erroring if it is not shaped like `B01.050`.
+    fn from_str(s: &str) -> Result<TreeCode, ValueError> {
+        if TreeCode::is_valid_str(s) {
+            Ok(TreeCode(s.to_string()))
+        } else {
+            log::debug!("Unexpected tree code: {}", s);
+            Err(ValueError)
+        }
+    }
+}
+
+impl TryFrom<String> for TreeCode {
+    type Error = ValueError;
+    fn try_from(s: String) -> Result<TreeCode, ValueError> {
+        if TreeCode::is_valid_str(&s) {
+            Ok(TreeCode(s))
+        } else {
+            Err(ValueError)
+        }
+    }
+}
+
+impl From<TreeCode> for String {
+    fn from(code: TreeCode) -> String {
+        code.0
+    }
+}
+
+impl Display for TreeCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Ord for TreeCode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.segments().cmp(other.segments())
+    }
+}
+
+impl PartialOrd for TreeCode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(s: &str) -> TreeCode {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_tree_code_parse() {
+        assert!("B01.050.500.131.365.400".parse::<TreeCode>().is_ok());
+        assert!("C04".parse::<TreeCode>().is_ok());
+        for invalid in ["", "B", "b01", "01.050", "B01.", "B01..050", "B01.05x"] {
+            assert!(invalid.parse::<TreeCode>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_tree_code_hierarchy() {
+        let c = code("B01.050.500.131");
+        assert_eq!(c.category(), 'B');
+        assert_eq!(c.depth(), 4);
+        assert_eq!(c.parent(), Some(code("B01.050.500")));
+        assert_eq!(code("B01").parent(), None);
+        assert_eq!(
+            c.ancestors(),
+            [code("B01"), code("B01.050"), code("B01.050.500")]
+        );
+        assert_eq!(c.root(), code("B01"));
+        assert!(c.is_descendant_of(&code("B01.050")));
+        assert!(!c.is_descendant_of(&c));
+        assert!(!code("B01.0501").is_descendant_of(&code("B01.050")));
+    }
+
+    #[test]
+    fn test_tree_code_ordering() {
+        let mut codes = vec![
+            code("B01.0501"),
+            code("B01.050.500"),
+            code("A01"),
+            code("B01.050"),
+        ];
+        codes.sort();
+        assert_eq!(
+            codes,
+            [
+                code("A01"),
+                code("B01.050"),
+                code("B01.050.500"),
+                code("B01.0501")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tree_code_serde() {
+        let c = code("B01.050");
+        assert_eq!(serde_json::to_string(&c).unwrap(), r#""B01.050""#);
+        assert_eq!(serde_json::from_str::<TreeCode>(r#""B01.050""#).unwrap(), c);
+        let lenient = serde_json::from_str::<TreeCode>(r#""nope""#).unwrap();
+        assert!(!lenient.is_valid());
+        assert!(c.is_valid());
+    }
+}
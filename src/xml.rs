@@ -0,0 +1,526 @@
+//! Parser for MetaMap's XML output (`--XMLf`, `--XMLn` and their `1` variants).
+//!
+//! The XML format describes the same output as [machine output](crate::machine),
+//! one `<MMO>` element per document:
+//!
+//! ```text
+//! <MMOs><MMO>
+//!   <CmdLine><Command>metamap --XMLf</Command></CmdLine>
+//!   <AAs Count="0"></AAs>
+//!   <Utterances Count="1"><Utterance>
+//!     <PMID>24119710</PMID><UttSection>ti</UttSection><UttNum>1</UttNum>
+//!     <UttText>Effects of heart attack</UttText><UttStartPos>0</UttStartPos><UttLength>23</UttLength>
+//!     <Phrases Count="2"><Phrase>...</Phrase></Phrases>
+//!   </Utterance></Utterances>
+//! </MMO></MMOs>
+//! ```
+//!
+//! [`XmlReader`] streams the documents of any [`BufRead`] as typed
+//! [`Mmo`]s, which [`Mmo::to_outputs`] projects onto fielded MMI records.
+
+use std::error;
+use std::fmt::{self, Display};
+use std::io::{self, BufRead};
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use crate::machine::{Abbreviation, Candidate, Mapping, Mmo, Phrase, SyntaxUnit, Utterance};
+use crate::Span;
+
+/// Errors produced while reading XML output with an [`XmlReader`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum XmlError {
+    /// The underlying reader failed. No further items are produced after this.
+    Io(io::Error),
+    /// The input is not well-formed XML. No further items are produced after this.
+    Syntax { position: u64, message: String },
+    /// An `<MMO>` element was readable but did not have the expected content.
+    /// Reading continues with the next document.
+    Structure { position: u64, message: String },
+}
+
+impl XmlError {
+    /// Byte offset in the input the error occurred at (the start of the
+    /// `<MMO>` element for structure errors), if any.
+    pub fn position(&self) -> Option<u64> {
+        match self {
+            XmlError::Io(_) => None,
+            XmlError::Syntax { position, .. } | XmlError::Structure { position, .. } => {
+                Some(*position)
+            }
+        }
+    }
+}
+
+impl Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            XmlError::Io(e) => write!(f, "could not read input: {}", e),
+            XmlError::Syntax { position, message } => {
+                write!(f, "invalid XML at byte {}: {}", position, message)
+            }
+            XmlError::Structure { position, message } => {
+                write!(f, "document at byte {}: {}", position, message)
+            }
+        }
+    }
+}
+
+impl error::Error for XmlError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            XmlError::Io(e) => Some(e),
+            XmlError::Syntax { .. } | XmlError::Structure { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for XmlError {
+    fn from(e: io::Error) -> Self {
+        XmlError::Io(e)
+    }
+}
+
+/// Whether `text` (the start of an input) looks like MetaMap XML output.
+pub fn looks_like_xml_output(text: &str) -> bool {
+    let start = text.trim_start();
+    ["<?xml", "<!DOCTYPE MMOs", "<MMOs"]
+        .iter()
+        .any(|prefix| start.starts_with(prefix))
+}
+
+/// An XML element with its text and child elements; attributes are not needed.
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    text: String,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn new(name: &[u8]) -> Self {
+        Element {
+            name: String::from_utf8_lossy(name).into_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// Children named `item` of the child named `list`, e.g. `<Sources><Source>`.
+    fn items<'a>(&'a self, list: &str, item: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.child(list)
+            .into_iter()
+            .flat_map(move |l| l.children.iter().filter(move |c| c.name == item))
+    }
+
+    fn texts(&self, list: &str, item: &str) -> Vec<String> {
+        self.items(list, item).map(|e| e.text.clone()).collect()
+    }
+
+    fn text(&self, name: &str) -> Result<&str, String> {
+        self.child(name)
+            .map(|c| c.text.as_str())
+            .ok_or_else(|| format!("missing <{}> in <{}>", name, self.name))
+    }
+
+    fn int(&self, name: &str) -> Result<i32, String> {
+        let text = self.text(name)?;
+        text.trim()
+            .parse()
+            .map_err(|_| format!("invalid number `{}` in <{}>", text, name))
+    }
+
+    /// `yes`/`no` flags, which newer versions also write as `1`/`0`.
+    fn flag(&self, name: &str) -> Result<bool, String> {
+        match self.text(name)?.trim() {
+            "yes" | "1" => Ok(true),
+            "no" | "0" => Ok(false),
+            other => Err(format!("invalid flag `{}` in <{}>", other, name)),
+        }
+    }
+}
+
+fn syntax_unit(element: &Element) -> Result<SyntaxUnit, String> {
+    Ok(SyntaxUnit {
+        kind: element.text("SyntaxType")?.to_string(),
+        input_match: element
+            .text("InputMatch")?
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+        tag: element.child("LexCat").map(|c| c.text.clone()),
+    })
+}
+
+fn candidate(element: &Element) -> Result<Candidate, String> {
+    let positions = element
+        .items("ConceptPIs", "ConceptPI")
+        .map(|pi| Ok(Span::new(pi.int("StartPos")?, pi.int("Length")?)))
+        .collect::<Result<_, String>>()?;
+    Ok(Candidate {
+        score: element.int("CandidateScore")?,
+        cui: element.text("CandidateCUI")?.to_string(),
+        matched: element.text("CandidateMatched")?.to_string(),
+        preferred_name: element.text("CandidatePreferred")?.to_string(),
+        matched_words: element.texts("MatchedWords", "MatchedWord"),
+        semantic_types: element.texts("SemTypes", "SemType"),
+        head: element.flag("IsHead")?,
+        overmatch: element.flag("IsOverMatch")?,
+        sources: element.texts("Sources", "Source"),
+        positions,
+        status: element.int("Status").ok(),
+        negated: element.flag("Negated").unwrap_or(false),
+    })
+}
+
+fn mapping(element: &Element) -> Result<Mapping, String> {
+    Ok(Mapping {
+        score: element.int("MappingScore")?,
+        candidates: element
+            .items("MappingCandidates", "Candidate")
+            .map(candidate)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+fn phrase(element: &Element) -> Result<Phrase, String> {
+    Ok(Phrase {
+        text: element.text("PhraseText")?.to_string(),
+        syntax: element
+            .items("SyntaxUnits", "SyntaxUnit")
+            .map(syntax_unit)
+            .collect::<Result<_, _>>()?,
+        start: element.int("PhraseStartPos")?,
+        length: element.int("PhraseLength")?,
+        candidates: element
+            .items("Candidates", "Candidate")
+            .map(candidate)
+            .collect::<Result<_, _>>()?,
+        mappings: element
+            .items("Mappings", "Mapping")
+            .map(mapping)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+fn utterance(element: &Element) -> Result<Utterance, String> {
+    Ok(Utterance {
+        id: format!(
+            "{}.{}.{}",
+            element.text("PMID")?,
+            element.text("UttSection")?,
+            element.int("UttNum")?
+        ),
+        text: element.text("UttText")?.to_string(),
+        start: element.int("UttStartPos")?,
+        length: element.int("UttLength")?,
+        phrases: element
+            .items("Phrases", "Phrase")
+            .map(phrase)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+fn abbreviation(element: &Element) -> Result<Abbreviation, String> {
+    Ok(Abbreviation {
+        short_form: element.text("AAText")?.to_string(),
+        long_form: element.text("AAExp")?.to_string(),
+        short_token_count: element.int("AATokenNum")?,
+        short_length: element.int("AALen")?,
+        long_token_count: element.int("AAExpTokenNum")?,
+        long_length: element.int("AAExpLen")?,
+        start: element.int("AAStartPos")?,
+    })
+}
+
+fn mmo(element: &Element) -> Result<Mmo, String> {
+    Ok(Mmo {
+        command_line: element
+            .child("CmdLine")
+            .and_then(|c| c.child("Command"))
+            .map(|c| c.text.clone()),
+        abbreviations: element
+            .items("AAs", "AA")
+            .map(abbreviation)
+            .collect::<Result<_, _>>()?,
+        utterances: element
+            .items("Utterances", "Utterance")
+            .map(utterance)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// Iterator over the documents (`<MMO>` elements) of XML output read from any
+/// [`BufRead`].
+///
+/// Only one document is held in memory at a time, and files written with
+/// `--XMLf1`/`--XMLn1` (one XML declaration per document) are read as well.
+///
+/// ```rust
+/// use mmi_parser::xml::XmlReader;
+///
+/// let input = "<MMOs><MMO><AAs Count=\"0\"></AAs><Utterances Count=\"0\"></Utterances></MMO></MMOs>";
+/// let documents = XmlReader::new(input.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(documents.len(), 1);
+/// ```
+pub struct XmlReader<R> {
+    reader: Reader<R>,
+    buffer: Vec<u8>,
+    finished: bool,
+}
+
+impl<R: BufRead> XmlReader<R> {
+    /// Creates a new reader over `reader`.
+    pub fn new(reader: R) -> Self {
+        let mut reader = Reader::from_reader(reader);
+        reader.config_mut().trim_text(true);
+        XmlReader {
+            reader,
+            buffer: Vec::new(),
+            finished: false,
+        }
+    }
+
+    fn error(&self, error: quick_xml::Error) -> XmlError {
+        match error {
+            quick_xml::Error::Io(e) => XmlError::Io(io::Error::new(e.kind(), e.to_string())),
+            e => XmlError::Syntax {
+                position: self.reader.error_position(),
+                message: e.to_string(),
+            },
+        }
+    }
+
+    /// Reads the content of an element whose start tag was just read, up to
+    /// and including its end tag.
+    fn read_element(&mut self, name: &[u8]) -> Result<Element, XmlError> {
+        let mut stack = vec![Element::new(name)];
+        loop {
+            self.buffer.clear();
+            let event = match self.reader.read_event_into(&mut self.buffer) {
+                Ok(event) => event,
+                Err(e) => return Err(self.error(e)),
+            };
+            let current = stack.last_mut().expect("the stack is never empty here");
+            match event {
+                Event::Start(e) => stack.push(Element::new(e.name().as_ref())),
+                Event::Empty(e) => current.children.push(Element::new(e.name().as_ref())),
+                Event::Text(e) => match e.unescape() {
+                    Ok(text) => current.text.push_str(&text),
+                    Err(e) => return Err(self.error(e)),
+                },
+                Event::CData(e) => current.text.push_str(&String::from_utf8_lossy(&e)),
+                Event::End(_) => {
+                    let element = stack.pop().expect("the stack is never empty here");
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(element),
+                        None => return Ok(element),
+                    }
+                }
+                Event::Eof => {
+                    return Err(XmlError::Syntax {
+                        position: self.reader.buffer_position(),
+                        message: "input ended inside of an <MMO> element".to_string(),
+                    })
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for XmlReader<R> {
+    type Item = Result<Mmo, XmlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            self.buffer.clear();
+            let position = self.reader.buffer_position();
+            match self.reader.read_event_into(&mut self.buffer) {
+                Ok(Event::Start(e)) if e.name().as_ref() == b"MMO" => {
+                    let result = self.read_element(b"MMO").and_then(|element| {
+                        mmo(&element).map_err(|message| XmlError::Structure { position, message })
+                    });
+                    if matches!(result, Err(XmlError::Io(_) | XmlError::Syntax { .. })) {
+                        self.finished = true;
+                    }
+                    return Some(result);
+                }
+                Ok(Event::Eof) => self.finished = true,
+                Ok(_) => continue,
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(self.error(e)));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Output;
+
+    const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE MMOs PUBLIC "-//NLM//DTD MetaMap Machine Output//EN" "http://metamap.nlm.nih.gov/DTD/MMOtoXML_v5.dtd">
+<MMOs>
+<MMO>
+<CmdLine>
+<Command>metamap --XMLf</Command>
+<Options Count="0"></Options>
+</CmdLine>
+<AAs Count="1">
+<AA>
+<AAText>MI</AAText>
+<AAExp>heart attack</AAExp>
+<AATokenNum>1</AATokenNum>
+<AALen>2</AALen>
+<AAExpTokenNum>3</AAExpTokenNum>
+<AAExpLen>12</AAExpLen>
+<AAStartPos>38</AAStartPos>
+<AACUIs Count="0"></AACUIs>
+</AA>
+</AAs>
+<Negations Count="0"></Negations>
+<Utterances Count="1">
+<Utterance>
+<PMID>24119710</PMID>
+<UttSection>ti</UttSection>
+<UttNum>1</UttNum>
+<UttText>Effects of heart attack &amp; MI</UttText>
+<UttStartPos>0</UttStartPos>
+<UttLength>28</UttLength>
+<Phrases Count="1">
+<Phrase>
+<PhraseText>of heart attack</PhraseText>
+<SyntaxUnits Count="2">
+<SyntaxUnit>
+<SyntaxType>prep</SyntaxType>
+<LexMatch>of</LexMatch>
+<InputMatch>of</InputMatch>
+<LexCat>prep</LexCat>
+<Tokens Count="1"><Token>of</Token></Tokens>
+</SyntaxUnit>
+<SyntaxUnit>
+<SyntaxType>head</SyntaxType>
+<LexMatch>heart attack</LexMatch>
+<InputMatch>heart attack</InputMatch>
+<LexCat>noun</LexCat>
+<Tokens Count="2"><Token>heart</Token><Token>attack</Token></Tokens>
+</SyntaxUnit>
+</SyntaxUnits>
+<PhraseStartPos>8</PhraseStartPos>
+<PhraseLength>15</PhraseLength>
+<Candidates Total="1" Excluded="0" Pruned="0" Remaining="1">
+<Candidate>
+<CandidateScore>-1000</CandidateScore>
+<CandidateCUI>C0027051</CandidateCUI>
+<CandidateMatched>Heart attack</CandidateMatched>
+<CandidatePreferred>Myocardial Infarction</CandidatePreferred>
+<MatchedWords Count="2"><MatchedWord>heart</MatchedWord><MatchedWord>attack</MatchedWord></MatchedWords>
+<SemTypes Count="1"><SemType>dsyn</SemType></SemTypes>
+<MatchMaps Count="0"></MatchMaps>
+<IsHead>yes</IsHead>
+<IsOverMatch>no</IsOverMatch>
+<Sources Count="2"><Source>MSH</Source><Source>MTH</Source></Sources>
+<ConceptPIs Count="1"><ConceptPI><StartPos>11</StartPos><Length>12</Length></ConceptPI></ConceptPIs>
+<Status>0</Status>
+<Negated>0</Negated>
+</Candidate>
+</Candidates>
+<Mappings Count="1">
+<Mapping>
+<MappingScore>-1000</MappingScore>
+<MappingCandidates Total="1">
+<Candidate>
+<CandidateScore>-1000</CandidateScore>
+<CandidateCUI>C0027051</CandidateCUI>
+<CandidateMatched>Heart attack</CandidateMatched>
+<CandidatePreferred>Myocardial Infarction</CandidatePreferred>
+<MatchedWords Count="2"><MatchedWord>heart</MatchedWord><MatchedWord>attack</MatchedWord></MatchedWords>
+<SemTypes Count="1"><SemType>dsyn</SemType></SemTypes>
+<MatchMaps Count="0"></MatchMaps>
+<IsHead>yes</IsHead>
+<IsOverMatch>no</IsOverMatch>
+<Sources Count="2"><Source>MSH</Source><Source>MTH</Source></Sources>
+<ConceptPIs Count="1"><ConceptPI><StartPos>11</StartPos><Length>12</Length></ConceptPI></ConceptPIs>
+<Status>0</Status>
+<Negated>1</Negated>
+</Candidate>
+</MappingCandidates>
+</Mapping>
+</Mappings>
+</Phrase>
+</Phrases>
+</Utterance>
+</Utterances>
+</MMO>
+</MMOs>
+"#;
+
+    #[test]
+    fn test_xml_reader() {
+        let documents = XmlReader::new(SAMPLE.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(documents.len(), 1);
+        let document = &documents[0];
+        assert_eq!(document.command_line.as_deref(), Some("metamap --XMLf"));
+        assert_eq!(document.document_id(), Some("24119710"));
+        assert_eq!(document.abbreviations[0].long_form, "heart attack");
+        let utterance = &document.utterances[0];
+        assert_eq!(utterance.id, "24119710.ti.1");
+        assert_eq!(utterance.text, "Effects of heart attack & MI");
+        let phrase = &utterance.phrases[0];
+        assert_eq!(phrase.syntax[1].input_match, ["heart", "attack"]);
+        assert_eq!(phrase.syntax[1].tag.as_deref(), Some("noun"));
+        let candidate = &phrase.candidates[0];
+        assert_eq!(candidate.score, -1000);
+        assert_eq!(candidate.sources, ["MSH", "MTH"]);
+        assert_eq!(candidate.positions, [Span::new(11, 12)]);
+        assert_eq!(candidate.status, Some(0));
+        assert!(candidate.head && !candidate.negated);
+        assert!(phrase.mappings[0].candidates[0].negated);
+    }
+
+    #[test]
+    fn test_xml_to_outputs() {
+        let document = XmlReader::new(SAMPLE.as_bytes()).next().unwrap().unwrap();
+        let outputs = document.to_outputs();
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(
+            outputs[0].to_string(),
+            "24119710|MMI|1000.00|Myocardial Infarction|C0027051|[dsyn]|[\"Heart attack\"-ti-1-\"heart attack\"-noun-1]|TI|11/12|"
+        );
+        match &outputs[1] {
+            Output::AA(aa) => {
+                assert_eq!(aa.to_string(), "24119710|AA|MI|heart attack|1|2|3|12|38:2")
+            }
+            other => panic!("expected an AA record, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_xml_reader_errors() {
+        let input =
+            "<MMOs><MMO><Utterances><Utterance><PMID>1</PMID></Utterance></Utterances></MMO>\
+                     <MMO><AAs/><Utterances/></MMO><MMO><Utterances></MMO>";
+        let items = XmlReader::new(input.as_bytes()).collect::<Vec<_>>();
+        assert_eq!(items.len(), 3);
+        assert!(matches!(
+            &items[0],
+            Err(XmlError::Structure { position: 6, message }) if message.contains("UttSection")
+        ));
+        assert!(items[1].as_ref().unwrap().utterances.is_empty());
+        assert!(matches!(items[2], Err(XmlError::Syntax { .. })));
+        assert!(looks_like_xml_output("\n<?xml version=\"1.0\"?>"));
+        assert!(!looks_like_xml_output("args('MetaMap',[])."));
+    }
+}
@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A fresh folder for the files of one test.
+fn test_folder(name: &str) -> PathBuf {
+    let folder = std::env::temp_dir().join(format!("mmi-parser-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&folder);
+    fs::create_dir_all(&folder).unwrap();
+    folder
+}
+
+fn run(folder: &Path, args: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_mmi-parser"))
+        .arg(folder)
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_same_stem_inputs() {
+    let folder = test_folder("same-stem");
+    fs::copy("data/MMI_sample.txt", folder.join("sample.txt")).unwrap();
+    fs::write(
+        folder.join("sample.xml"),
+        "<?xml version=\"1.0\"?>\n<MMOs></MMOs>\n",
+    )
+    .unwrap();
+    fs::write(folder.join("sample.json"), "{\"unrelated\": true}\n").unwrap();
+
+    run(&folder, &[]);
+    let parsed = fs::read_to_string(folder.join("sample_parsed.jsonl")).unwrap();
+    assert_eq!(parsed.lines().count(), 7);
+    assert!(!folder.join("sample.xml_parsed.jsonl").exists());
+    assert!(!folder.join("sample.json_parsed.jsonl").exists());
+
+    run(&folder, &["--input-format", "xml"]);
+    let xml = fs::read_to_string(folder.join("sample.xml_parsed.jsonl")).unwrap();
+    assert!(xml.is_empty());
+    assert_eq!(
+        fs::read_to_string(folder.join("sample_parsed.jsonl")).unwrap(),
+        parsed
+    );
+    fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn test_combined_jobs_keep_input_order() {
+    let folder = test_folder("combined-jobs");
+    let sample = fs::read_to_string("data/MMI_sample.txt").unwrap();
+    let (_, fields) = sample.lines().next().unwrap().split_once('|').unwrap();
+    // the first file is by far the largest, so it finishes last
+    for (i, count) in [5000, 1, 10, 1, 100, 1].into_iter().enumerate() {
+        let record = format!("{}|{}", i + 1, fields);
+        let contents = vec![record.as_str(); count].join("\n");
+        fs::write(folder.join(format!("{}.txt", i)), contents).unwrap();
+    }
+    let combined = folder.join("combined.jsonl");
+    run(
+        &folder,
+        &["--combined", combined.to_str().unwrap(), "--jobs", "4"],
+    );
+    let ids = fs::read_to_string(&combined)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            record["MMI"]["id"].as_str().unwrap().to_string()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(ids.len(), 5113);
+    let mut expected = ids.clone();
+    expected.sort();
+    assert_eq!(ids, expected);
+    assert_eq!(ids.first().unwrap(), "1");
+    assert_eq!(ids.last().unwrap(), "6");
+    fs::remove_dir_all(&folder).unwrap();
+}